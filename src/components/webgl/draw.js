@@ -34,28 +34,23 @@ export default function initDraw (canvas, triangles, points, circumcenters, seaL
 
   // Rivers
   const riverCap = 1;
-  const riverSegments = rivers.reduce((sum, next) => sum + next.length - 1, 0) - 1;
-  const riverPoints = rivers.flatMap(river =>
-    river.flatMap((part, i, arr) => {
-      if (i === arr.length - 1) return [];
-      const index1 = part[0];
-      const x1 = circumcenters[index1 * 2 + 0];
-      const y1 = circumcenters[index1 * 2 + 1];
-      const z1 = zScale(heights[index1]);
-      const index2 = arr[i + 1][0];
-      const x2 = circumcenters[index2 * 2 + 0];
-      const y2 = circumcenters[index2 * 2 + 1];
-      const z2 = zScale(heights[index2]);
-      return [x1, y1, z1, x2, y2, z2];
-    })
-  );
-
-  const flux = rivers.flatMap(river =>
-    river.flatMap((part, i, arr) => {
-      if (i === arr.length - 1) return [];
-      return [i === 0 ? arr[i + 1][1] : part[1], arr[i + 1][1]];
-    })
-  );
+  // Rivers are CSR: river `r` is `nodes[offsets[r]..offsets[r + 1]]`, with its
+  // flux alongside. Each segment is drawn between two consecutive nodes.
+  const { offsets, nodes, flux: nodeFlux } = rivers;
+  const riverSegments = nodes.length - (offsets.length - 1) - 1;
+  const riverPoints = [];
+  const flux = [];
+  for (let r = 0; r < offsets.length - 1; r++) {
+    for (let i = offsets[r]; i < offsets[r + 1] - 1; i++) {
+      const index1 = nodes[i];
+      const index2 = nodes[i + 1];
+      riverPoints.push(
+        circumcenters[index1 * 2 + 0], circumcenters[index1 * 2 + 1], zScale(heights[index1]),
+        circumcenters[index2 * 2 + 0], circumcenters[index2 * 2 + 1], zScale(heights[index2]),
+      );
+      flux.push(i === offsets[r] ? nodeFlux[i + 1] : nodeFlux[i], nodeFlux[i + 1]);
+    }
+  }
 
   const riverBuffer = regl.buffer(riverPoints)
   const fluxBuffer = regl.buffer(flux)
@@ -4,37 +4,88 @@ const wasm = WASM();
 
 class TerrainGenerator {
   constructor (seed=123456) {
+    this.seed = seed;
     this.wasm = new Promise((resolve, reject) => wasm
       .then(result => {
-        this.terrainGen = new result.TerrainGenerator(seed);
+        this.module = result;
         resolve(true);
       }).catch(reject)
     );
   }
 
-  async generate ({ points = 2**10, seaLevel = 0.39 }={}) {
+  async generate ({ points = 2**10, seaLevel = 0.39 }={}, onProgress=() => {}) {
     await this.wasm;
+    const { TerrainGenerator, WorldBuilder, WorldOptions } = this.module;
+    const options = new WorldOptions(seaLevel);
 
-    let radius = Math.pow(500 / points, 0.5) / 10;
-    const world = this.terrainGen.world(radius, seaLevel).as_js_value();
-    world.seaLevel = seaLevel;
-    world.points           = world.voronoi.delaunay.points;
-    world.circumcenters    = world.voronoi.circumcenters;
-    world.voronoiAdjacency = world.voronoi.adjacent;
-    world.voronoiTriangles = world.voronoi.voronoi_triangles;
-    world.voronoiPoints    = world.voronoi.voronoi_points;
+    // Keep the builder between calls with the same point count, so only the
+    // stages downstream of a changed option are re-run.
+    if (!this.builder || this.points !== points) {
+      this.free();
+      let radius = Math.pow(500 / points, 0.5) / 10;
+      this.terrainGen = new TerrainGenerator(this.seed);
+      this.builder = WorldBuilder.withOptions(radius, options);
+      this.builder.onProgress(onProgress);
+      this.points = points;
+    } else {
+      this.builder.setOptions(options);
+    }
+    let generated;
+    try {
+      generated = this.builder.finish(this.terrainGen);
+    } catch (error) {
+      // Start over with a fresh builder next time
+      this.free();
+      throw error;
+    }
 
-    delete world.voronoi
+    // Getters return views into wasm memory, so copy each one out with `slice`
+    // before anything else runs on the Rust side. Lists of lists stay CSR:
+    // list `i` is `values[offsets[i]..offsets[i + 1]]`.
+    const world = { seaLevel: generated.seaLevel };
+    for (const key of ARRAYS) world[key] = generated[key].slice();
+
+    generated.free();
     return world;
   }
+
+  // Releases the wasm memory held by the builder and generator
+  free () {
+    if (this.builder) {
+      this.builder.free();
+      this.terrainGen.free();
+      this.builder = undefined;
+    }
+  }
 }
 
+const ARRAYS = [
+  'points', 'circumcenters', 'heights', 'cellHeights', 'voronoiTriangles',
+  'voronoiAdjacencyOffsets', 'voronoiAdjacency', 'voronoiPointsOffsets', 'voronoiPoints',
+  'coastLines', 'riverOffsets', 'riverNodes', 'riverFlux',
+];
+
+let generator;
 addEventListener('message', async function (event) {
   const { action, payload } = event.data;
   if (action === 'generate') {
     const { seed, options } = payload;
-    const generator = new TerrainGenerator(seed);
-    const world = await generator.generate(options);
-    postMessage(world);
+    if (!generator || generator.seed !== seed) {
+      if (generator) generator.free();
+      generator = new TerrainGenerator(seed);
+    }
+    const onProgress = (stage, progress) => postMessage({
+      action: 'progress',
+      payload: { stage, progress }
+    });
+    let world;
+    try {
+      world = await generator.generate(options, onProgress);
+    } catch (error) {
+      postMessage({ action: 'error', payload: { message: error.message } });
+      return;
+    }
+    const buffers = ARRAYS.map(key => world[key].buffer);
+    postMessage({ action: 'world', payload: world }, buffers);
   }
 });
\ No newline at end of file
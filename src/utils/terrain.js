@@ -1,16 +1,19 @@
 const worker = new Worker('./terrain-worker.js');
 
-const resolveResponse = worker => new Promise(resolve => {
+const resolveResponse = (worker, onProgress) => new Promise((resolve, reject) => {
   function resolver (event) {
-    resolve(event.data);
+    const { action, payload } = event.data;
+    if (action === 'progress') return onProgress(payload);
+    if (action === 'error') reject(new Error(payload.message));
+    else resolve(payload);
     worker.removeEventListener('message', resolver);
   } 
   worker.addEventListener('message', resolver);
 });
 
 
-async function generate ({ seed = 1234, points = 2**10, seaLevel = 0.39 }={}) {
-  const response = resolveResponse(worker);
+async function generate ({ seed = 1234, points = 2**10, seaLevel = 0.39, onProgress = () => {} }={}) {
+  const response = resolveResponse(worker, onProgress);
   worker.postMessage({
     action: 'generate',
     payload: {
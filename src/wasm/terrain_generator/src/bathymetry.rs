@@ -0,0 +1,211 @@
+use super::graph::Graph;
+use super::noise::Noise;
+use super::voronoi::Voronoi;
+
+pub struct BathymetryOptions {
+    // Distance from the coast where the shelf gives way to the slope, and
+    // where the slope reaches the abyssal plain
+    pub shelf_width: f64,
+    pub slope_width: f64,
+    // Depths below the sea at the edge of the shelf and on the plain
+    pub shelf_depth: f64,
+    pub abyssal_depth: f64,
+    // Share of the old sea floor's relief kept on top
+    pub relief: f64,
+    // Trenches along plate boundaries out in the ocean
+    pub trenches: bool,
+    pub trench_width: f64,
+    pub trench_depth: f64,
+}
+
+impl Default for BathymetryOptions {
+    fn default() -> BathymetryOptions {
+        BathymetryOptions {
+            shelf_width: 0.03,
+            slope_width: 0.04,
+            shelf_depth: 0.02,
+            abyssal_depth: 0.2,
+            relief: 0.2,
+            trenches: false,
+            trench_width: 0.015,
+            trench_depth: 0.12,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DepthClass {
+    Land,
+    Shelf,
+    Slope,
+    Abyssal,
+    Trench,
+}
+
+fn get_distances(
+    sources: &[usize],
+    graph: &Graph,
+    position: &dyn Fn(usize) -> [f64; 2],
+) -> Vec<f64> {
+    graph
+        .nearest(sources, position)
+        .into_iter()
+        .map(|(d, _)| d)
+        .collect()
+}
+
+pub fn smoothstep(t: f64) -> f64 {
+    let t = t.clamp(0., 1.);
+    t * t * (3. - 2. * t)
+}
+
+// Reshapes the sea floor by distance from the coast: a shallow shelf, a slope
+// down from its edge, and the abyssal plain beyond, with some of the old relief
+// kept on top. Trenches follow plate boundaries where they cross deep ocean.
+//
+// Only nodes out in open water are moved, never ones on a coast, so land cells
+// keep their heights and the coast lines stay where they are.
+pub fn shape_sea_floor(
+    heights: &mut [f64],
+    cell_heights: &mut [f64],
+    voronoi: &Voronoi,
+    noise: &Noise,
+    sea_level: f64,
+    options: &BathymetryOptions,
+) {
+    let circumcenters = &voronoi.circumcenters;
+    let node = |t: usize| [circumcenters[t * 2], circumcenters[t * 2 + 1]];
+
+    let is_open = |t: usize| {
+        voronoi.voronoi_cells[t]
+            .iter()
+            .all(|&c| cell_heights[c] < sea_level)
+    };
+    let coast = (0..heights.len())
+        .filter(|&t| !is_open(t))
+        .collect::<Vec<_>>();
+    let distances = get_distances(&coast, &voronoi.adjacent, &node);
+
+    let plates = (0..heights.len())
+        .map(|t| noise.plate(node(t)[0], node(t)[1]))
+        .collect::<Vec<_>>();
+    let boundaries = (0..heights.len())
+        .filter(|&t| voronoi.adjacent[t].iter().any(|&n| plates[n] != plates[t]))
+        .collect::<Vec<_>>();
+    let to_boundary = if options.trenches {
+        get_distances(&boundaries, &voronoi.adjacent, &node)
+    } else {
+        vec![f64::INFINITY; heights.len()]
+    };
+
+    let original = heights.to_vec();
+    let ocean = options.shelf_width + options.slope_width;
+    for t in 0..heights.len() {
+        if !is_open(t) {
+            continue;
+        }
+        let d = distances[t];
+        let mut depth = if d < options.shelf_width {
+            options.shelf_depth * d / options.shelf_width
+        } else {
+            let u = smoothstep((d - options.shelf_width) / options.slope_width);
+            options.shelf_depth + (options.abyssal_depth - options.shelf_depth) * u
+        };
+
+        // Trenches fade in over the slope, so they stay off the shelf
+        let closeness = 1. - to_boundary[t] / options.trench_width;
+        if closeness > 0. {
+            let oceanic = smoothstep((d - options.shelf_width) / (ocean - options.shelf_width));
+            depth += options.trench_depth * smoothstep(closeness) * oceanic;
+        }
+
+        let floor = sea_level - depth;
+        let height = floor * (1. - options.relief) + original[t].min(sea_level) * options.relief;
+        heights[t] = height.min(sea_level - f64::EPSILON).max(0.);
+    }
+
+    // Cells are the mean of their corners, as in `get_cell_heights`. A sea cell
+    // whose floor rose above the sea gets its old corners back.
+    for (cell, points) in voronoi.voronoi_points.iter().enumerate() {
+        if cell_heights[cell] >= sea_level || points.is_empty() {
+            continue;
+        }
+        let mean =
+            |heights: &[f64]| points.iter().map(|&n| heights[n]).sum::<f64>() / points.len() as f64;
+        if mean(heights) >= sea_level {
+            for &n in points.iter() {
+                heights[n] = original[n];
+            }
+        }
+        cell_heights[cell] = mean(heights);
+    }
+}
+
+// Depth classes of cells, from their depth below the sea. Only the depths in
+// `options` matter, and worlds are always built with the default ones.
+pub fn get_depth_classes(
+    cell_heights: &[f64],
+    sea_level: f64,
+    options: &BathymetryOptions,
+) -> Vec<DepthClass> {
+    let shelf = options.shelf_depth * 1.5;
+    let abyssal = options.abyssal_depth * 0.75;
+    let trench = options.abyssal_depth + options.trench_depth * 0.5;
+
+    cell_heights
+        .iter()
+        .map(|&height| {
+            let depth = sea_level - height;
+            if depth <= 0. {
+                DepthClass::Land
+            } else if depth < shelf {
+                DepthClass::Shelf
+            } else if depth < abyssal {
+                DepthClass::Slope
+            } else if depth < trench {
+                DepthClass::Abyssal
+            } else {
+                DepthClass::Trench
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depth_class_thresholds() {
+        let options = BathymetryOptions::default();
+        // Shelf below 0.03, slope below 0.15, abyssal plain below 0.26
+        let table = [
+            (0.1, DepthClass::Land),
+            (0., DepthClass::Land),
+            (-0.001, DepthClass::Shelf),
+            (-0.029, DepthClass::Shelf),
+            (-0.031, DepthClass::Slope),
+            (-0.149, DepthClass::Slope),
+            (-0.151, DepthClass::Abyssal),
+            (-0.259, DepthClass::Abyssal),
+            (-0.261, DepthClass::Trench),
+            (-1., DepthClass::Trench),
+        ];
+        let heights = table.iter().map(|&(h, _)| h).collect::<Vec<_>>();
+        let classes = get_depth_classes(&heights, 0., &options);
+        for (&(height, expected), class) in table.iter().zip(classes) {
+            assert_eq!(class, expected, "at height {}", height);
+        }
+    }
+
+    #[test]
+    fn depth_classes_follow_the_sea_level() {
+        let options = BathymetryOptions::default();
+        let classes = get_depth_classes(&vec![0.38, 0.4, 0.5], 0.39, &options);
+        assert_eq!(
+            classes,
+            vec![DepthClass::Shelf, DepthClass::Land, DepthClass::Land]
+        );
+    }
+}
@@ -1,10 +1,289 @@
-fn main() {
-    let mut terrain_gen = terrain_generator::terrain_generator::TerrainGenerator::new(None);
+#[macro_use]
+extern crate serde_derive;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use terrain_generator::terrain_generator::{TerrainGenerator, World, WorldBuilder, WorldOptions};
+
+const USAGE: &str = "Usage: main [options]
+
+Options:
+    --seed N            Seed of the first world (default 123456)
+    --points N          Approximate number of points (default 8192)
+    --sea-level F       Sea level between 0 and 1 (default 0.39)
+    --erosion-passes N  Number of erosion passes (default 10)
+    --erosion-rate F    Erosion per pass (default 0.015)
+    --river-threshold F Minimum flux for a river to be kept (default 0)
+    --river-min-length N
+                        Minimum nodes in a river (default 2)
+    --river-min-order N Minimum Strahler order of a river (default 1)
+    --rivers-reach-water
+                        Keep only rivers that end in the sea or a sink
+    --no-river-mouths   Don't shape deltas and estuaries
+    --mouth-min-discharge F
+                        Discharge at a river mouth to shape it, as a fraction
+                        of all circumcenters (default 0.002)
+    --delta-threshold F Sediment load per unit of coastal slope from which a
+                        mouth is a delta rather than an estuary (default 0.005)
+    --coast-erosion-passes N
+                        Passes of the sea carving bays into coasts (default 0)
+    --no-bathymetry     Don't shape shelves, slopes and abyssal plains
+    --trenches          Cut trenches along plate boundaries in the ocean
+    --south-latitude F  Latitude of the bottom edge of the map (default 15)
+    --north-latitude F  Latitude of the top edge of the map (default 75)
+    --axial-tilt F      Degrees, for how strong the seasons are (default 23.44)
+    --glacial-erosion   Carve U-shaped valleys and moraines under glaciers
+    --config FILE       JSON file with any of the options above, in snake_case
+    --out DIR           Directory to write worlds to (default .)
+    --format LIST       Comma separated: json, geojson, svg, obj, glb, bin (default svg)
+    --batch N           Generate N worlds, from `seed` to `seed + N - 1` (default 1)
+    --help              Show this message";
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+struct Config {
+    seed: u32,
+    points: u32,
+    sea_level: f64,
+    erosion_passes: usize,
+    erosion_rate: f64,
+    river_threshold: f64,
+    river_min_length: usize,
+    river_min_order: u32,
+    rivers_reach_water: bool,
+    river_mouths: bool,
+    mouth_min_discharge: f64,
+    delta_threshold: f64,
+    coast_erosion_passes: usize,
+    bathymetry: bool,
+    trenches: bool,
+    south_latitude: f64,
+    north_latitude: f64,
+    axial_tilt: f64,
+    glacial_erosion: bool,
+    out: PathBuf,
+    formats: Vec<String>,
+    batch: u32,
+}
 
-    let points = 2u32.pow(13);
-    let radius = (500.0 / points as f64).sqrt() / 10.0;
+impl Default for Config {
+    fn default() -> Config {
+        let options = WorldOptions::new(0.39);
+        Config {
+            seed: 123456,
+            points: 2u32.pow(13),
+            sea_level: options.sea_level,
+            erosion_passes: options.erosion_passes,
+            erosion_rate: options.erosion_rate,
+            river_threshold: options.river_threshold,
+            river_min_length: options.river_min_length,
+            river_min_order: options.river_min_order,
+            rivers_reach_water: options.rivers_reach_water,
+            river_mouths: options.river_mouths,
+            mouth_min_discharge: options.mouth_min_discharge,
+            delta_threshold: options.delta_threshold,
+            coast_erosion_passes: options.coast_erosion_passes,
+            bathymetry: options.bathymetry,
+            trenches: options.trenches,
+            south_latitude: options.south_latitude,
+            north_latitude: options.north_latitude,
+            axial_tilt: options.axial_tilt,
+            glacial_erosion: options.glacial_erosion,
+            out: PathBuf::from("."),
+            formats: vec!["svg".to_string()],
+            batch: 1,
+        }
+    }
+}
+
+fn parse<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or(format!("{} needs a value", flag))?;
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {}: {}", flag, value))
+}
+
+fn parse_args(args: Vec<String>) -> Result<Config, String> {
+    // The config file is read first, so flags can override it
+    let mut config = match args.iter().position(|a| a == "--config") {
+        Some(i) => {
+            let path = args.get(i + 1).ok_or("--config needs a value")?;
+            let file = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+            serde_json::from_str(&file).map_err(|e| format!("{}: {}", path, e))?
+        }
+        None => Config::default(),
+    };
+
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--seed" => config.seed = parse(&flag, args.next())?,
+            "--points" => config.points = parse(&flag, args.next())?,
+            "--sea-level" => config.sea_level = parse(&flag, args.next())?,
+            "--erosion-passes" => config.erosion_passes = parse(&flag, args.next())?,
+            "--erosion-rate" => config.erosion_rate = parse(&flag, args.next())?,
+            "--river-threshold" => config.river_threshold = parse(&flag, args.next())?,
+            "--river-min-length" => config.river_min_length = parse(&flag, args.next())?,
+            "--river-min-order" => config.river_min_order = parse(&flag, args.next())?,
+            "--rivers-reach-water" => config.rivers_reach_water = true,
+            "--no-river-mouths" => config.river_mouths = false,
+            "--mouth-min-discharge" => config.mouth_min_discharge = parse(&flag, args.next())?,
+            "--delta-threshold" => config.delta_threshold = parse(&flag, args.next())?,
+            "--coast-erosion-passes" => config.coast_erosion_passes = parse(&flag, args.next())?,
+            "--no-bathymetry" => config.bathymetry = false,
+            "--trenches" => config.trenches = true,
+            "--south-latitude" => config.south_latitude = parse(&flag, args.next())?,
+            "--north-latitude" => config.north_latitude = parse(&flag, args.next())?,
+            "--axial-tilt" => config.axial_tilt = parse(&flag, args.next())?,
+            "--glacial-erosion" => config.glacial_erosion = true,
+            "--out" => config.out = parse(&flag, args.next())?,
+            "--batch" => config.batch = parse(&flag, args.next())?,
+            "--format" => {
+                let list: String = parse(&flag, args.next())?;
+                config.formats = list.split(',').map(|f| f.trim().to_string()).collect();
+            }
+            "--config" => {
+                args.next();
+            }
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => return Err(format!("Unknown option: {}", flag)),
+        }
+    }
+
+    for format in config.formats.iter() {
+        match format.as_str() {
+            "json" | "geojson" | "svg" | "obj" | "glb" | "bin" => {}
+            _ => return Err(format!("Unknown format: {}", format)),
+        }
+    }
+    Ok(config)
+}
 
-    let world = terrain_gen.world(radius, 30.0);
+fn generate(seed: u32, config: &Config) -> Result<World, String> {
+    let radius = (500.0 / config.points as f64).sqrt() / 10.0;
+    let options = WorldOptions {
+        sea_level: config.sea_level,
+        erosion_passes: config.erosion_passes,
+        erosion_rate: config.erosion_rate,
+        river_threshold: config.river_threshold,
+        river_min_length: config.river_min_length,
+        river_min_order: config.river_min_order,
+        rivers_reach_water: config.rivers_reach_water,
+        river_mouths: config.river_mouths,
+        mouth_min_discharge: config.mouth_min_discharge,
+        delta_threshold: config.delta_threshold,
+        coast_erosion_passes: config.coast_erosion_passes,
+        bathymetry: config.bathymetry,
+        trenches: config.trenches,
+        south_latitude: config.south_latitude,
+        north_latitude: config.north_latitude,
+        axial_tilt: config.axial_tilt,
+        glacial_erosion: config.glacial_erosion,
+    };
+
+    let mut terrain_gen = TerrainGenerator::new(Some(seed));
+    WorldBuilder::with_options(radius, options)
+        .build(&mut terrain_gen)
+        .map_err(|e| format!("Seed {}: {}", seed, e))
+}
+
+fn write(world: &World, seed: u32, format: &str, out: &Path) -> Result<(), String> {
+    let extension = match format {
+        "bin" => "world",
+        "geojson" => "geojson",
+        format => format,
+    };
+    let path = out.join(format!("world-{}.{}", seed, extension));
+
+    let result = match format {
+        "json" => fs::write(&path, serde_json::to_string(world).unwrap()),
+        "geojson" => fs::write(&path, world.to_geojson()),
+        "svg" => fs::write(&path, world.to_svg()),
+        "obj" => fs::write(&path, world.to_obj()),
+        "glb" => fs::write(&path, world.to_glb()),
+        "bin" => fs::write(&path, world.save(true)),
+        _ => unreachable!(),
+    };
+    result.map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// Generates and writes every world in the batch. A seed that fails is logged
+// and marked failed in the summary, and the rest still run. Returns how many
+// failed.
+fn run(config: Config) -> Result<u32, String> {
+    fs::create_dir_all(&config.out).map_err(|e| format!("{}: {}", config.out.display(), e))?;
+
+    let header = "seed,status,cells,land_fraction,rivers,landmasses,largest_landmass";
+    let mut report = vec![header.to_string()];
+    let mut failed = 0;
+    println!(
+        "{:>10} {:>7} {:>6} {:>7} {:>10} {:>8}",
+        "seed", "cells", "land", "rivers", "landmasses", "largest"
+    );
+
+    for seed in config.seed..config.seed.saturating_add(config.batch) {
+        let world = generate(seed, &config).and_then(|world| {
+            for format in config.formats.iter() {
+                write(&world, seed, format, &config.out)
+                    .map_err(|e| format!("Seed {}: {}", seed, e))?;
+            }
+            Ok(world)
+        });
+        let world = match world {
+            Ok(world) => world,
+            Err(error) => {
+                eprintln!("{}", error);
+                println!("{:>10} {:>7}", seed, "failed");
+                report.push(format!("{},failed,,,,,", seed));
+                failed += 1;
+                continue;
+            }
+        };
+
+        let stats = world.stats();
+        println!(
+            "{:>10} {:>7} {:>5.1}% {:>7} {:>10} {:>8}",
+            seed,
+            stats.cells,
+            stats.land_fraction * 100.,
+            stats.rivers,
+            stats.landmasses,
+            stats.largest_landmass
+        );
+        report.push(format!(
+            "{},ok,{},{},{},{},{}",
+            seed,
+            stats.cells,
+            stats.land_fraction,
+            stats.rivers,
+            stats.landmasses,
+            stats.largest_landmass
+        ));
+    }
+
+    let path = config.out.join("summary.csv");
+    fs::write(&path, report.join("\n") + "\n").map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(failed)
+}
+
+fn main() {
+    let result = parse_args(env::args().skip(1).collect()).and_then(run);
 
-    println!("{:?}", world);
+    match result {
+        Ok(0) => {}
+        Ok(failed) => {
+            eprintln!("{} world(s) failed", failed);
+            process::exit(1);
+        }
+        Err(error) => {
+            eprintln!("{}\nRun with --help for usage.", error);
+            process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,249 @@
+use super::graph::Graph;
+use miniz_oxide::deflate::compress_to_vec;
+use miniz_oxide::inflate::decompress_to_vec_with_limit;
+
+// Layout of a saved world:
+//
+//     magic    b"TGW\0"
+//     version  u8
+//     flags    u8          bit 0 set when the body is deflated
+//     body     [u8]
+//
+// The body is a fixed sequence of sections. Floats are little-endian `f64`s,
+// indices are LEB128 varints, and lists of lists are stored CSR-style as a
+// varint length per list followed by all the flattened entries. Indices that
+// may be `EMPTY` are stored shifted by one, with zero meaning `EMPTY`.
+//
+// Version 2 appended the river mouths, version 3 the climate options, version 4
+// the resources, and version 5 the seed. Older versions still load, without
+// mouths, with the default climate, with no fertile land or deposits, and with
+// seed 0.
+
+pub const MAGIC: &[u8; 4] = b"TGW\0";
+pub const VERSION: u8 = 5;
+const DEFLATE: u8 = 1;
+// Largest body inflated, so a small corrupt file can't run the worker out of
+// memory. Worlds take about 250 bytes a cell, so this still fits a quarter
+// million cells, far more than the app generates.
+const MAX_BODY: usize = 64 << 20;
+
+pub struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    pub fn new() -> Writer {
+        Writer { buf: Vec::new() }
+    }
+
+    pub fn varint(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.buf.push(byte);
+                return;
+            }
+            self.buf.push(byte | 0x80);
+        }
+    }
+
+    pub fn f64(&mut self, value: f64) {
+        self.buf.extend(&value.to_le_bytes());
+    }
+
+    pub fn f64s(&mut self, values: &[f64]) {
+        self.varint(values.len() as u64);
+        for &value in values.iter() {
+            self.f64(value);
+        }
+    }
+
+    pub fn indices(&mut self, values: &[usize]) {
+        self.varint(values.len() as u64);
+        for &value in values.iter() {
+            self.varint(value as u64);
+        }
+    }
+
+    pub fn optional_indices(&mut self, values: &[usize], empty: usize) {
+        self.varint(values.len() as u64);
+        for &value in values.iter() {
+            self.varint(if value == empty { 0 } else { value as u64 + 1 });
+        }
+    }
+
+    pub fn lists(&mut self, lists: &[Vec<usize>]) {
+        self.varint(lists.len() as u64);
+        for list in lists.iter() {
+            self.varint(list.len() as u64);
+        }
+        for list in lists.iter() {
+            for &value in list.iter() {
+                self.varint(value as u64);
+            }
+        }
+    }
+
+    // Same layout as `lists`, straight from the CSR arrays
+    pub fn graph(&mut self, graph: &Graph) {
+        self.varint(graph.len() as u64);
+        for w in graph.offsets().windows(2) {
+            self.varint((w[1] - w[0]) as u64);
+        }
+        for &value in graph.values().iter() {
+            self.varint(value as u64);
+        }
+    }
+
+    pub fn finish(self, compress: bool) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+        if compress {
+            out.push(DEFLATE);
+            out.extend(compress_to_vec(&self.buf, 6));
+        } else {
+            out.push(0);
+            out.extend(self.buf);
+        }
+        out
+    }
+}
+
+pub struct Reader {
+    pub version: u8,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl Reader {
+    pub fn new(bytes: &[u8]) -> Result<Reader, String> {
+        if bytes.len() < 6 || &bytes[..4] != MAGIC {
+            return Err("Not a terrain world".to_string());
+        }
+        let version = bytes[4];
+        if version == 0 || version > VERSION {
+            return Err(format!("Unsupported world version {}", bytes[4]));
+        }
+
+        let buf = if bytes[5] & DEFLATE != 0 {
+            decompress_to_vec_with_limit(&bytes[6..], MAX_BODY)
+                .map_err(|_| "Corrupt compressed world".to_string())?
+        } else {
+            bytes[6..].to_vec()
+        };
+
+        Ok(Reader {
+            version,
+            buf,
+            pos: 0,
+        })
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self.buf.get(self.pos).ok_or("Unexpected end of world")?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    pub fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift >= 64 {
+                return Err("Varint overflow".to_string());
+            }
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    fn index(&mut self) -> Result<usize, String> {
+        let value = self.varint()?;
+        if value > usize::MAX as u64 {
+            return Err("Index out of range for this platform".to_string());
+        }
+        Ok(value as usize)
+    }
+
+    fn len(&mut self) -> Result<usize, String> {
+        let len = self.index()?;
+        // Every entry takes at least one byte, so anything longer is corrupt
+        if len > self.buf.len() - self.pos {
+            return Err("Length exceeds world size".to_string());
+        }
+        Ok(len)
+    }
+
+    pub fn f64(&mut self) -> Result<f64, String> {
+        if self.pos + 8 > self.buf.len() {
+            return Err("Unexpected end of world".to_string());
+        }
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&self.buf[self.pos..self.pos + 8]);
+        self.pos += 8;
+        Ok(f64::from_le_bytes(bytes))
+    }
+
+    pub fn f64s(&mut self) -> Result<Vec<f64>, String> {
+        let len = self.len()?;
+        (0..len).map(|_| self.f64()).collect()
+    }
+
+    pub fn indices(&mut self) -> Result<Vec<usize>, String> {
+        let len = self.len()?;
+        (0..len).map(|_| self.index()).collect()
+    }
+
+    pub fn optional_indices(&mut self, empty: usize) -> Result<Vec<usize>, String> {
+        let len = self.len()?;
+        (0..len)
+            .map(|_| match self.index()? {
+                0 => Ok(empty),
+                value => Ok(value - 1),
+            })
+            .collect()
+    }
+
+    pub fn lists(&mut self) -> Result<Vec<Vec<usize>>, String> {
+        let len = self.len()?;
+        let lengths = (0..len)
+            .map(|_| self.len())
+            .collect::<Result<Vec<_>, _>>()?;
+        lengths
+            .into_iter()
+            .map(|n| (0..n).map(|_| self.index()).collect())
+            .collect()
+    }
+
+    pub fn graph(&mut self) -> Result<Graph, String> {
+        let len = self.len()?;
+        let lengths = (0..len)
+            .map(|_| self.len())
+            .collect::<Result<Vec<_>, _>>()?;
+        let values = lengths.iter().sum();
+        if values > self.buf.len() - self.pos {
+            return Err("Length exceeds world size".to_string());
+        }
+        let mut graph = Graph::with_capacity(len, values);
+        for n in lengths {
+            graph.push(
+                (0..n)
+                    .map(|_| self.index())
+                    .collect::<Result<Vec<_>, _>>()?,
+            );
+        }
+        Ok(graph)
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        if self.pos != self.buf.len() {
+            return Err("Trailing bytes after world".to_string());
+        }
+        Ok(())
+    }
+}
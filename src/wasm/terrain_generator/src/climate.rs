@@ -0,0 +1,848 @@
+use super::graph::Graph;
+use super::river_network::RiverNetwork;
+
+// Distance, in the unit square, over which currents carry their water's
+// temperature before it takes on the latitude's
+const CURRENT_REACH: f64 = 0.25;
+// Distance inland over which the sea's warmth or cold and its moisture fade
+const MARITIME_REACH: f64 = 0.06;
+const CONTINENTAL_REACH: f64 = 0.3;
+
+// Sea surface, and air at sea level, at the equator and the poles, in °C
+const SEA_EQUATOR: f64 = 28.;
+const SEA_POLE: f64 = -2.;
+const AIR_EQUATOR: f64 = 27.;
+const AIR_POLE: f64 = -25.;
+// °C colder per unit of height between the sea and the highest peak
+const LAPSE: f64 = 32.;
+
+// Half the yearly temperature swing, in °C at the poles with Earth's tilt, by
+// the sea and deep inland
+const MARITIME_SWING: f64 = 5.;
+const CONTINENTAL_SWING: f64 = 25.;
+const EARTH_TILT: f64 = 23.44;
+// Snow melted per month for every °C above freezing, in mm of water
+const MELT_RATE: f64 = 60.;
+
+pub const MONTHS: usize = 12;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ClimateOptions {
+    // Latitudes of the bottom and top edges of the map, in degrees. North is
+    // towards increasing y.
+    pub south: f64,
+    pub north: f64,
+    // Degrees, which decides how strong the seasons are
+    #[serde(rename = "axialTilt")]
+    pub axial_tilt: f64,
+}
+
+impl Default for ClimateOptions {
+    fn default() -> ClimateOptions {
+        ClimateOptions {
+            south: 15.,
+            north: 75.,
+            axial_tilt: EARTH_TILT,
+        }
+    }
+}
+
+impl ClimateOptions {
+    fn latitude(&self, y: f64) -> f64 {
+        (self.south + (self.north - self.south) * y).to_radians()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Biome {
+    Ocean,
+    Ice,
+    Tundra,
+    Taiga,
+    TemperateForest,
+    TemperateRainforest,
+    Grassland,
+    Desert,
+    Savanna,
+    TropicalRainforest,
+}
+
+// Köppen climate classes, in the usual order. Ocean cells are classified too,
+// by the air above them.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Koppen {
+    Af,
+    Am,
+    Aw,
+    #[serde(rename = "BWh")]
+    Bwh,
+    #[serde(rename = "BWk")]
+    Bwk,
+    #[serde(rename = "BSh")]
+    Bsh,
+    #[serde(rename = "BSk")]
+    Bsk,
+    Csa,
+    Csb,
+    Csc,
+    Cwa,
+    Cwb,
+    Cwc,
+    Cfa,
+    Cfb,
+    Cfc,
+    Dsa,
+    Dsb,
+    Dsc,
+    Dsd,
+    Dwa,
+    Dwb,
+    Dwc,
+    Dwd,
+    Dfa,
+    Dfb,
+    Dfc,
+    Dfd,
+    #[serde(rename = "ET")]
+    Et,
+    #[serde(rename = "EF")]
+    Ef,
+}
+
+// By dry season (summer, winter, none) and then by summer warmth
+const TEMPERATE: [[Koppen; 3]; 3] = [
+    [Koppen::Csa, Koppen::Csb, Koppen::Csc],
+    [Koppen::Cwa, Koppen::Cwb, Koppen::Cwc],
+    [Koppen::Cfa, Koppen::Cfb, Koppen::Cfc],
+];
+const CONTINENTAL: [[Koppen; 4]; 3] = [
+    [Koppen::Dsa, Koppen::Dsb, Koppen::Dsc, Koppen::Dsd],
+    [Koppen::Dwa, Koppen::Dwb, Koppen::Dwc, Koppen::Dwd],
+    [Koppen::Dfa, Koppen::Dfb, Koppen::Dfc, Koppen::Dfd],
+];
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Climate {
+    pub options: ClimateOptions,
+    // Surface current of each cell, as x and y pairs like `points`. Zero on land.
+    pub currents: Vec<f64>,
+    // Sea surface temperature in °C. On land, that of the nearest sea.
+    #[serde(rename = "seaTemperatures")]
+    pub sea_temperatures: Vec<f64>,
+    // Mean air temperature in °C, and yearly precipitation in mm
+    pub temperatures: Vec<f64>,
+    pub precipitation: Vec<f64>,
+    pub biomes: Vec<Biome>,
+
+    // Twelve values per cell, January first: mean temperature in °C,
+    // precipitation in mm, and snow on the ground at the month's end in mm of
+    // water
+    #[serde(rename = "monthlyTemperatures")]
+    pub monthly_temperatures: Vec<f64>,
+    #[serde(rename = "monthlyPrecipitation")]
+    pub monthly_precipitation: Vec<f64>,
+    pub snow: Vec<f64>,
+    pub koppen: Vec<Koppen>,
+
+    // Land under ice that never melts, and the height above which that would
+    // be, per cell
+    pub glaciers: Vec<bool>,
+    #[serde(rename = "snowLine")]
+    pub snow_line: Vec<f64>,
+
+    // Twelve factors per river, averaging one, to scale its discharge by month
+    pub regimes: Vec<f64>,
+}
+
+impl Climate {
+    // Winds by latitude drive the currents, the currents carry warm and cold
+    // water along the coasts, and the sea's temperature then sets how mild and
+    // how wet the land near it is.
+    //
+    // The seasons then swing each cell's temperature around its mean, more so
+    // towards the poles and inland, and move the rain belts after the sun.
+    pub fn new(
+        points: &Vec<f64>,
+        neighbors: &Graph,
+        voronoi_cells: &Graph,
+        cell_heights: &Vec<f64>,
+        network: &RiverNetwork,
+        sea_level: f64,
+        options: &ClimateOptions,
+    ) -> Climate {
+        let cells = cell_heights.len();
+        let is_sea = |i: usize| cell_heights[i] < sea_level;
+        let site = |i: usize| [points[i * 2], points[i * 2 + 1]];
+        let latitude = |i: usize| options.latitude(points[i * 2 + 1]);
+
+        let currents = get_currents(points, neighbors, cell_heights, sea_level, options);
+        let sea_temperatures = get_sea_temperatures(
+            points,
+            neighbors,
+            cell_heights,
+            sea_level,
+            &currents,
+            options,
+        );
+
+        // Land feels the sea it is nearest to, and less of it further inland
+        let seas = (0..cells).filter(|&i| is_sea(i)).collect::<Vec<_>>();
+        let nearest = neighbors.nearest(&seas, &site);
+
+        let mut climate = Climate {
+            options: *options,
+            currents,
+            sea_temperatures: Vec::with_capacity(cells),
+            temperatures: Vec::with_capacity(cells),
+            precipitation: Vec::with_capacity(cells),
+            biomes: Vec::with_capacity(cells),
+            monthly_temperatures: Vec::with_capacity(cells * MONTHS),
+            monthly_precipitation: Vec::with_capacity(cells * MONTHS),
+            snow: Vec::with_capacity(cells * MONTHS),
+            koppen: Vec::with_capacity(cells),
+            glaciers: Vec::with_capacity(cells),
+            snow_line: Vec::with_capacity(cells),
+            regimes: Vec::new(),
+        };
+        let mut runoff = Vec::with_capacity(cells * MONTHS);
+        for i in 0..cells {
+            let lat = latitude(i);
+            let (distance, sea) = nearest[i];
+            let has_sea = sea != usize::MAX;
+            let sea_temperature = if has_sea {
+                sea_temperatures[sea]
+            } else {
+                sea_baseline(lat)
+            };
+
+            // How much warmer or colder the nearest sea is than usual there
+            let anomaly = if has_sea {
+                sea_temperature - sea_baseline(latitude(sea))
+            } else {
+                0.
+            };
+            let elevation = elevation(cell_heights[i], sea_level);
+            let maritime = libm::exp(-distance / MARITIME_REACH);
+            let temperature = air_baseline(lat) - LAPSE * elevation + anomaly * maritime;
+
+            // Warm seas evaporate more, and warm currents more than cold ones.
+            // Moisture blown onshore reaches further.
+            let warmth = (sea_temperature - SEA_POLE) / (SEA_EQUATOR - SEA_POLE);
+            let evaporation = (0.5 + 0.5 * warmth + anomaly / 8.).clamp(0.1, 1.2);
+            let onshore = if has_sea && distance > 0. {
+                let (a, b) = (site(sea), site(i));
+                let wind = wind(lat);
+                let along = (wind[0] * (b[0] - a[0]) + wind[1] * (b[1] - a[1])) / distance;
+                0.5 + 0.5 * along.max(0.) / libm::hypot(wind[0], wind[1]).max(f64::EPSILON)
+            } else {
+                1.
+            };
+            let moisture = evaporation * libm::exp(-distance / (CONTINENTAL_REACH * onshore));
+            let precipitation = rainfall(lat) * (0.2 + 0.8 * moisture);
+
+            // Ice stays where even the warmest month freezes
+            let swing = swing(lat, distance, options);
+            let warmest = temperature + swing;
+            let glacier = !is_sea(i) && warmest < 0.;
+            let snow_line = sea_level + (warmest / LAPSE + elevation).max(0.) * (1. - sea_level);
+
+            let biome = if is_sea(i) {
+                Biome::Ocean
+            } else if glacier {
+                Biome::Ice
+            } else {
+                get_biome(temperature, precipitation)
+            };
+
+            // Seasons, with the rain belts following the sun north and south
+            let mut temperatures = [0.; MONTHS];
+            let mut shares = [0.; MONTHS];
+            for m in 0..MONTHS {
+                let season = season(m);
+                temperatures[m] = temperature + lat.signum() * season * swing;
+                let shift = options.axial_tilt.to_radians() * season / 2.;
+                shares[m] = rainfall(lat - shift);
+            }
+            let total = shares.iter().sum::<f64>();
+            let precipitations = shares.map(|share| precipitation * share / total);
+            let (snow, melted) = get_snow(&temperatures, &precipitations);
+
+            climate.sea_temperatures.push(sea_temperature);
+            climate.temperatures.push(temperature);
+            climate.precipitation.push(precipitation);
+            climate.biomes.push(biome);
+            climate
+                .koppen
+                .push(get_koppen(&temperatures, &precipitations, lat >= 0.));
+            climate
+                .monthly_temperatures
+                .extend_from_slice(&temperatures);
+            climate
+                .monthly_precipitation
+                .extend_from_slice(&precipitations);
+            climate.snow.extend_from_slice(&snow);
+            climate.glaciers.push(glacier);
+            climate.snow_line.push(snow_line);
+            runoff.extend_from_slice(&melted);
+        }
+
+        climate.regimes = get_regimes(network, voronoi_cells, &runoff);
+        climate
+    }
+}
+
+// Temperature in the warmest month, without the currents' warming or cooling,
+// as a first guess at where glaciers are before the climate is known
+pub fn get_summer_temperatures(
+    points: &[f64],
+    neighbors: &Graph,
+    cell_heights: &[f64],
+    sea_level: f64,
+    options: &ClimateOptions,
+) -> Vec<f64> {
+    let site = |i: usize| [points[i * 2], points[i * 2 + 1]];
+    let seas = (0..cell_heights.len())
+        .filter(|&i| cell_heights[i] < sea_level)
+        .collect::<Vec<_>>();
+    let nearest = neighbors.nearest(&seas, &site);
+
+    (0..cell_heights.len())
+        .map(|i| {
+            let lat = options.latitude(points[i * 2 + 1]);
+            air_baseline(lat) - LAPSE * elevation(cell_heights[i], sea_level)
+                + swing(lat, nearest[i].0, options)
+        })
+        .collect()
+}
+
+// Height above the sea, from 0 at the coast to 1 at the highest possible peak
+fn elevation(height: f64, sea_level: f64) -> f64 {
+    ((height - sea_level) / (1. - sea_level)).max(0.)
+}
+
+// Half the yearly temperature swing, growing towards the poles and inland
+fn swing(latitude: f64, distance: f64, options: &ClimateOptions) -> f64 {
+    let continentality = 1. - libm::exp(-distance / CONTINENTAL_REACH);
+    seasons(options)
+        * libm::sin(latitude.abs())
+        * (MARITIME_SWING + (CONTINENTAL_SWING - MARITIME_SWING) * continentality)
+}
+
+// Strength of the seasons relative to Earth's
+fn seasons(options: &ClimateOptions) -> f64 {
+    libm::sin(options.axial_tilt.to_radians()) / libm::sin(EARTH_TILT.to_radians())
+}
+
+// From -1 in January to 1 in July, the northern summer
+fn season(month: usize) -> f64 {
+    -libm::cos(2. * std::f64::consts::PI * month as f64 / MONTHS as f64)
+}
+
+// Snow left at the end of each month, and the water running off in it, in the
+// second year, after the first has built up a winter's snowpack. Where it never
+// melts it holds two years of snowfall.
+fn get_snow(
+    temperatures: &[f64; MONTHS],
+    precipitation: &[f64; MONTHS],
+) -> ([f64; MONTHS], [f64; MONTHS]) {
+    let mut pack = 0.;
+    let mut snow = [0.; MONTHS];
+    let mut runoff = [0.; MONTHS];
+    for _ in 0..2 {
+        for m in 0..MONTHS {
+            if temperatures[m] < 0. {
+                pack += precipitation[m];
+                runoff[m] = 0.;
+            } else {
+                let melt = (MELT_RATE * temperatures[m]).min(pack);
+                pack -= melt;
+                runoff[m] = precipitation[m] + melt;
+            }
+            snow[m] = pack;
+        }
+    }
+    (snow, runoff)
+}
+
+// Monthly factors for each river's discharge, from the runoff of the cells
+// along it and all its tributaries
+fn get_regimes(network: &RiverNetwork, voronoi_cells: &Graph, runoff: &[f64]) -> Vec<f64> {
+    let streams = &network.streams;
+    let mut totals = vec![[0.; MONTHS]; streams.len()];
+
+    // Tributaries come after the stream they join
+    for s in (0..streams.len()).rev() {
+        let stream = &streams[s];
+        let first_owned = if stream.parent.is_some() { 1 } else { 0 };
+        let mut total = [0.; MONTHS];
+        for &node in stream.nodes.iter().skip(first_owned) {
+            for &cell in voronoi_cells[node].iter() {
+                for (m, sum) in total.iter_mut().enumerate() {
+                    *sum += runoff[cell * MONTHS + m];
+                }
+            }
+        }
+        for &t in stream.tributaries.iter() {
+            for (m, sum) in total.iter_mut().enumerate() {
+                *sum += totals[t][m];
+            }
+        }
+        totals[s] = total;
+    }
+
+    totals
+        .iter()
+        .flat_map(|total| {
+            let mean = total.iter().sum::<f64>() / MONTHS as f64;
+            total.map(|t| if mean > 0. { t / mean } else { 1. })
+        })
+        .collect()
+}
+
+// Köppen's rules, on a year of monthly temperatures and precipitation
+fn get_koppen(
+    temperatures: &[f64; MONTHS],
+    precipitation: &[f64; MONTHS],
+    northern: bool,
+) -> Koppen {
+    let coldest = temperatures.iter().cloned().fold(f64::INFINITY, f64::min);
+    let warmest = temperatures
+        .iter()
+        .cloned()
+        .fold(f64::NEG_INFINITY, f64::max);
+    let mean = temperatures.iter().sum::<f64>() / MONTHS as f64;
+    let total = precipitation.iter().sum::<f64>();
+    let driest = precipitation.iter().cloned().fold(f64::INFINITY, f64::min);
+
+    // April to September is summer in the north, winter in the south
+    let is_summer = |m: usize| (3..9).contains(&m) == northern;
+    let half = |summer: bool| (0..MONTHS).filter(move |&m| is_summer(m) == summer);
+    let summer_rain = half(true).map(|m| precipitation[m]).sum::<f64>();
+    let wettest = |summer: bool| half(summer).map(|m| precipitation[m]).fold(0., f64::max);
+    let driest_in = |summer: bool| {
+        half(summer)
+            .map(|m| precipitation[m])
+            .fold(f64::INFINITY, f64::min)
+    };
+
+    if warmest < 10. {
+        return if warmest < 0. { Koppen::Ef } else { Koppen::Et };
+    }
+
+    let threshold = 20. * mean
+        + if summer_rain >= 0.7 * total {
+            280.
+        } else if summer_rain >= 0.3 * total {
+            140.
+        } else {
+            0.
+        };
+    if total < threshold {
+        let hot = mean >= 18.;
+        return match (total < threshold / 2., hot) {
+            (true, true) => Koppen::Bwh,
+            (true, false) => Koppen::Bwk,
+            (false, true) => Koppen::Bsh,
+            (false, false) => Koppen::Bsk,
+        };
+    }
+
+    if coldest >= 18. {
+        return if driest >= 60. {
+            Koppen::Af
+        } else if driest >= 100. - total / 25. {
+            Koppen::Am
+        } else {
+            Koppen::Aw
+        };
+    }
+
+    let dry = if driest_in(true) < 40. && driest_in(true) < wettest(false) / 3. {
+        0
+    } else if driest_in(false) < wettest(true) / 10. {
+        1
+    } else {
+        2
+    };
+    let warm_months = temperatures.iter().filter(|&&t| t >= 10.).count();
+    let summer = if warmest >= 22. {
+        0
+    } else if warm_months >= 4 {
+        1
+    } else if coldest > -38. {
+        2
+    } else {
+        3
+    };
+
+    if coldest > -3. {
+        TEMPERATE[dry][summer.min(2)]
+    } else {
+        CONTINENTAL[dry][summer]
+    }
+}
+
+// Prevailing wind at a latitude: easterly trades towards the equator,
+// westerlies in the mid latitudes, and polar easterlies
+fn wind(latitude: f64) -> [f64; 2] {
+    let u = -libm::cos(3. * latitude);
+    let v = -0.3 * latitude.signum() * libm::sin(6. * latitude.abs());
+    [u, v]
+}
+
+// Latitude at the middle of the gyre a current at `latitude` turns around:
+// the subtropical ones between the trades and the westerlies, and the
+// subpolar ones between the westerlies and the polar easterlies
+fn gyre_center(latitude: f64) -> f64 {
+    let center: f64 = if latitude.abs() < 45_f64.to_radians() {
+        30.
+    } else {
+        60.
+    };
+    center.to_radians() * latitude.signum()
+}
+
+// Mean temperatures by latitude, roughly as on Earth
+fn sea_baseline(latitude: f64) -> f64 {
+    let c = libm::cos(latitude);
+    SEA_POLE + (SEA_EQUATOR - SEA_POLE) * c * libm::sqrt(c)
+}
+
+fn air_baseline(latitude: f64) -> f64 {
+    AIR_POLE + (AIR_EQUATOR - AIR_POLE) * libm::cos(latitude)
+}
+
+// Yearly precipitation in mm over a wet sea at `latitude`: rainy at the
+// equator and along the polar fronts, dry under the subtropical highs
+fn rainfall(latitude: f64) -> f64 {
+    let degrees = latitude.to_degrees().abs();
+    let band = |center: f64, width: f64| libm::exp(-((degrees - center) / width).powi(2));
+    400. + 2000. * band(0., 12.) + 900. * band(50., 15.)
+}
+
+// Whittaker's biomes, by mean temperature and yearly precipitation
+fn get_biome(temperature: f64, precipitation: f64) -> Biome {
+    if temperature < -10. {
+        Biome::Ice
+    } else if temperature < 0. {
+        Biome::Tundra
+    } else if temperature < 5. {
+        if precipitation < 250. {
+            Biome::Tundra
+        } else {
+            Biome::Taiga
+        }
+    } else if temperature < 20. {
+        if precipitation < 250. {
+            Biome::Desert
+        } else if precipitation < 600. {
+            Biome::Grassland
+        } else if precipitation < 1500. {
+            Biome::TemperateForest
+        } else {
+            Biome::TemperateRainforest
+        }
+    } else if precipitation < 300. {
+        Biome::Desert
+    } else if precipitation < 1200. {
+        Biome::Savanna
+    } else {
+        Biome::TropicalRainforest
+    }
+}
+
+// Passes for a current or its water to travel `CURRENT_REACH`, one cell a pass
+fn current_passes(cells: usize) -> usize {
+    ((CURRENT_REACH * (cells as f64).sqrt()).ceil() as usize).max(1)
+}
+
+// Wind driven surface currents over the sea. Where the wind pushes water into
+// land it turns along the coast towards its gyre's center, poleward on the
+// western side of an ocean and back towards the equator on its eastern side,
+// and smoothing spreads those boundary currents offshore.
+pub fn get_currents(
+    points: &[f64],
+    neighbors: &Graph,
+    cell_heights: &[f64],
+    sea_level: f64,
+    options: &ClimateOptions,
+) -> Vec<f64> {
+    let cells = cell_heights.len();
+    let is_sea = |i: usize| cell_heights[i] < sea_level;
+    let site = |i: usize| [points[i * 2], points[i * 2 + 1]];
+
+    // Unit vector from each coastal sea cell towards the land around it
+    let shores = (0..cells)
+        .map(|i| {
+            if !is_sea(i) {
+                return [0., 0.];
+            }
+            let a = site(i);
+            let mut normal = [0., 0.];
+            for &n in neighbors[i].iter().filter(|&&n| !is_sea(n)) {
+                let b = site(n);
+                let length = libm::hypot(b[0] - a[0], b[1] - a[1]).max(f64::EPSILON);
+                normal[0] += (b[0] - a[0]) / length;
+                normal[1] += (b[1] - a[1]) / length;
+            }
+            let length = libm::hypot(normal[0], normal[1]);
+            if length > 0. {
+                [normal[0] / length, normal[1] / length]
+            } else {
+                [0., 0.]
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let winds = (0..cells)
+        .map(|i| wind(options.latitude(points[i * 2 + 1])))
+        .collect::<Vec<_>>();
+    let mut currents = vec![[0., 0.]; cells];
+    for i in (0..cells).filter(|&i| is_sea(i)) {
+        currents[i] = winds[i];
+    }
+
+    for _ in 0..current_passes(cells) {
+        let mut next = vec![[0., 0.]; cells];
+        for i in (0..cells).filter(|&i| is_sea(i)) {
+            let mut mean = [0., 0.];
+            let mut count = 0;
+            for &n in neighbors[i].iter().filter(|&&n| is_sea(n)) {
+                mean[0] += currents[n][0];
+                mean[1] += currents[n][1];
+                count += 1;
+            }
+            let mut current = winds[i];
+            if count > 0 {
+                current[0] = (current[0] + mean[0] / count as f64) / 2.;
+                current[1] = (current[1] + mean[1] / count as f64) / 2.;
+            }
+
+            // Turn what flows into the land along the coast instead
+            let normal = shores[i];
+            let into = current[0] * normal[0] + current[1] * normal[1];
+            if into > 0. {
+                let latitude = options.latitude(points[i * 2 + 1]);
+                let towards = gyre_center(latitude) - latitude;
+                let mut tangent = [-normal[1], normal[0]];
+                if tangent[1] * towards * (options.north - options.south) < 0. {
+                    tangent = [-tangent[0], -tangent[1]];
+                }
+                current[0] += into * (tangent[0] - normal[0]);
+                current[1] += into * (tangent[1] - normal[1]);
+            }
+            next[i] = current;
+        }
+        currents = next;
+    }
+
+    currents.into_iter().flatten().collect()
+}
+
+// Sea surface temperatures, each cell taking on the water flowing into it from
+// upstream, and slowly the temperature of its own latitude
+pub fn get_sea_temperatures(
+    points: &[f64],
+    neighbors: &Graph,
+    cell_heights: &[f64],
+    sea_level: f64,
+    currents: &[f64],
+    options: &ClimateOptions,
+) -> Vec<f64> {
+    let cells = cell_heights.len();
+    let is_sea = |i: usize| cell_heights[i] < sea_level;
+    let site = |i: usize| [points[i * 2], points[i * 2 + 1]];
+    let baselines = (0..cells)
+        .map(|i| sea_baseline(options.latitude(points[i * 2 + 1])))
+        .collect::<Vec<_>>();
+
+    let passes = current_passes(cells);
+    let relax = 1. / passes as f64;
+    let mut temperatures = baselines.clone();
+    for _ in 0..passes {
+        let mut next = temperatures.clone();
+        for i in (0..cells).filter(|&i| is_sea(i)) {
+            let a = site(i);
+            let (mut sum, mut weights) = (0., 0.);
+            for &n in neighbors[i].iter().filter(|&&n| is_sea(n)) {
+                let b = site(n);
+                let length = libm::hypot(a[0] - b[0], a[1] - b[1]).max(f64::EPSILON);
+                let flow = (currents[n * 2] * (a[0] - b[0]) + currents[n * 2 + 1] * (a[1] - b[1]))
+                    / length;
+                if flow > 0. {
+                    sum += flow * temperatures[n];
+                    weights += flow;
+                }
+            }
+            let speed = libm::hypot(currents[i * 2], currents[i * 2 + 1]).min(1.);
+            let upstream = if weights > 0. {
+                sum / weights
+            } else {
+                temperatures[i]
+            };
+            let carried = speed * upstream + (1. - speed) * temperatures[i];
+            next[i] = relax * baselines[i] + (1. - relax) * carried;
+        }
+        temperatures = next;
+    }
+
+    temperatures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `n` by `n` cells on a square grid over the unit square, each joined to the
+    // four beside it
+    fn grid(n: usize) -> (Vec<f64>, Graph) {
+        let step = 1. / (n - 1) as f64;
+        let mut points = Vec::with_capacity(n * n * 2);
+        let mut lists = Vec::with_capacity(n * n);
+        for row in 0..n {
+            for col in 0..n {
+                points.extend(&[col as f64 * step, row as f64 * step]);
+                let mut list = Vec::new();
+                if col > 0 {
+                    list.push(row * n + col - 1);
+                }
+                if col + 1 < n {
+                    list.push(row * n + col + 1);
+                }
+                if row > 0 {
+                    list.push((row - 1) * n + col);
+                }
+                if row + 1 < n {
+                    list.push((row + 1) * n + col);
+                }
+                lists.push(list);
+            }
+        }
+        (points, Graph::from_lists(&lists))
+    }
+
+    #[test]
+    fn currents_follow_the_wind_over_open_sea() {
+        let n = 11;
+        let (points, neighbors) = grid(n);
+        let options = ClimateOptions::default();
+        let currents = get_currents(&points, &neighbors, &vec![0.; n * n], 0.5, &options);
+
+        // Trades flow west at 15°, westerlies east at 45°
+        let middle = n / 2;
+        assert!(currents[middle * 2] < 0.);
+        assert!(currents[(middle * n + middle) * 2] > 0.);
+    }
+
+    #[test]
+    fn currents_turn_along_a_western_coast() {
+        let n = 11;
+        let (points, neighbors) = grid(n);
+        let options = ClimateOptions::default();
+        // Land along the western edge
+        let cell_heights = (0..n * n)
+            .map(|i| if i % n == 0 { 1. } else { 0. })
+            .collect::<Vec<_>>();
+        let currents = get_currents(&points, &neighbors, &cell_heights, 0.5, &options);
+
+        for row in 0..n {
+            assert_eq!(currents[row * n * 2..row * n * 2 + 2], [0., 0.]);
+        }
+        // The trades pile water against the coast and it turns poleward, north
+        // here, where the open sea flows south
+        let coast = n + 1;
+        let open = n + n / 2;
+        assert!(currents[coast * 2 + 1] > 0.);
+        assert!(currents[open * 2 + 1] < 0.);
+
+        // South when the map runs from the southern pole up to the tropics
+        let options = ClimateOptions {
+            south: -75.,
+            north: -15.,
+            ..options
+        };
+        let currents = get_currents(&points, &neighbors, &cell_heights, 0.5, &options);
+        let coast = (n - 2) * n + 1;
+        assert!(currents[coast * 2 + 1] < 0.);
+    }
+
+    #[test]
+    fn still_sea_takes_its_latitudes_temperature() {
+        let n = 11;
+        let (points, neighbors) = grid(n);
+        let options = ClimateOptions::default();
+        let currents = vec![0.; n * n * 2];
+        let temperatures = get_sea_temperatures(
+            &points,
+            &neighbors,
+            &vec![0.; n * n],
+            0.5,
+            &currents,
+            &options,
+        );
+
+        for (i, &t) in temperatures.iter().enumerate() {
+            let expected = sea_baseline(options.latitude(points[i * 2 + 1]));
+            assert!((t - expected).abs() < 1e-9);
+            assert!(t > SEA_POLE && t < SEA_EQUATOR);
+        }
+        // Colder towards the pole
+        assert!(temperatures[0] > temperatures[n * n - 1]);
+    }
+
+    #[test]
+    fn currents_carry_their_waters_temperature() {
+        let n = 11;
+        let (points, neighbors) = grid(n);
+        let options = ClimateOptions::default();
+        let cell_heights = vec![0.; n * n];
+        let sea = |v: f64| {
+            let currents = (0..n * n).flat_map(|_| vec![0., v]).collect::<Vec<_>>();
+            get_sea_temperatures(&points, &neighbors, &cell_heights, 0.5, &currents, &options)
+        };
+
+        // Poleward water is warmer than its latitude, and equatorward colder
+        let cell = (n - 1) * n + n / 2;
+        let baseline = sea_baseline(options.latitude(points[cell * 2 + 1]));
+        assert!(sea(1.)[cell] > baseline + 1.);
+        assert!(sea(-1.)[0] < sea_baseline(options.latitude(0.)) - 1.);
+    }
+
+    // April to September at `summer`, the other months at `winter`
+    fn year(summer: f64, winter: f64) -> [f64; MONTHS] {
+        let mut months = [winter; MONTHS];
+        for month in months.iter_mut().take(9).skip(3) {
+            *month = summer;
+        }
+        months
+    }
+
+    #[test]
+    fn koppen_classes() {
+        // Monthly temperatures and precipitation, northern hemisphere or not
+        let table = [
+            (year(27., 27.), year(200., 200.), true, Koppen::Af),
+            (year(27., 27.), year(300., 40.), true, Koppen::Am),
+            (year(25., 25.), year(200., 10.), true, Koppen::Aw),
+            (year(30., 30.), year(10., 10.), true, Koppen::Bwh),
+            (year(10., 10.), year(25., 25.), true, Koppen::Bsk),
+            (year(25., 10.), year(5., 100.), true, Koppen::Csa),
+            (year(10., 25.), year(100., 5.), false, Koppen::Csa),
+            (year(17., 5.), year(60., 60.), true, Koppen::Cfb),
+            (year(25., -10.), year(150., 5.), true, Koppen::Dwa),
+            (year(18., -10.), year(50., 50.), true, Koppen::Dfb),
+            (year(5., -20.), year(30., 30.), true, Koppen::Et),
+            (year(-5., -30.), year(20., 20.), true, Koppen::Ef),
+        ];
+        for (temperatures, precipitation, northern, expected) in table.iter() {
+            assert_eq!(
+                get_koppen(temperatures, precipitation, *northern),
+                *expected,
+                "for {:?} and {:?}",
+                temperatures,
+                precipitation
+            );
+        }
+    }
+}
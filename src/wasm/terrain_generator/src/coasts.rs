@@ -1,32 +1,28 @@
-use std::collections::HashSet;
+use super::error::TerrainError;
+use super::graph::Graph;
+use super::noise::DetailNoise;
+use super::parallel::{filter_range, map_range};
+use super::voronoi::Voronoi;
+use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
 
-fn get_coast_cells(heights: &Vec<f64>, neighbors: &Vec<Vec<usize>>, sea_level: f64) -> Vec<usize> {
-    let mut coasts = Vec::new();
-
-    for i in 0..heights.len() {
-        if heights[i] >= sea_level {
-            let neighbors_sea = neighbors[i].iter().any(|&n| heights[n] < sea_level);
-            if neighbors_sea {
-                coasts.push(i);
-            }
-        }
-    }
-
-    coasts
+fn get_coast_cells(heights: &Vec<f64>, neighbors: &Graph, sea_level: f64) -> Vec<usize> {
+    filter_range(heights.len(), |i| {
+        heights[i] >= sea_level && neighbors[i].iter().any(|&n| heights[n] < sea_level)
+    })
 }
 
 pub fn get_coast_lines(
     heights: &Vec<f64>,
-    neighbors: &Vec<Vec<usize>>,
-    voronoi_points: &Vec<Vec<usize>>,
-    voronoi_cells: &Vec<Vec<usize>>,
+    neighbors: &Graph,
+    voronoi_points: &Graph,
+    voronoi_cells: &Graph,
     sea_level: f64,
 ) -> Vec<(usize, usize)> {
     let coast_cells = get_coast_cells(heights, neighbors, sea_level);
-    let mut coast_lines = Vec::new();
 
-    for k in 0..coast_cells.len() {
+    let cell_lines = |k: usize| {
+        let mut coast_lines = Vec::new();
         let points = &voronoi_points[coast_cells[k]];
         let mut prev = points[points.len() - 1];
         let mut prev_is_border = voronoi_cells[prev].iter().any(|&c| heights[c] < sea_level);
@@ -57,7 +53,422 @@ pub fn get_coast_lines(
             prev = point;
             prev_is_border = is_border;
         }
+        coast_lines
+    };
+
+    map_range(coast_cells.len(), cell_lines)
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+// Rings of cells around a coast cell that its exposure is measured over
+const EXPOSURE_RINGS: usize = 3;
+// Onshore slopes from which a shore is rocky, and then a cliff
+const ROCKY_SLOPE: f64 = 1.;
+const CLIFF_SLOPE: f64 = 3.;
+// Exposure below which a gentle shore is sheltered enough for a marsh
+const MARSH_EXPOSURE: f64 = 0.35;
+// Erodibility, exposure times softness, from which the sea takes a coast cell
+const ERODIBLE: f64 = 0.3;
+// Depth below the sea that eroded cells are sunk to
+const ERODED_DEPTH: f64 = 0.004;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CoastKind {
+    Beach,
+    Cliff,
+    Marsh,
+    Rocky,
+}
+
+// Fraction of the cells within a few rings of `cell` that are sea. Headlands
+// and straight coasts are exposed; bays and lagoons are sheltered.
+pub fn get_exposure(cell: usize, heights: &[f64], neighbors: &Graph, sea_level: f64) -> f64 {
+    let mut seen = HashSet::new();
+    seen.insert(cell);
+    let mut ring = vec![cell];
+    let mut sea = 0;
+
+    for _ in 0..EXPOSURE_RINGS {
+        let mut next = Vec::new();
+        for &c in ring.iter() {
+            for &n in neighbors[c].iter() {
+                if seen.insert(n) {
+                    if heights[n] < sea_level {
+                        sea += 1;
+                    }
+                    next.push(n);
+                }
+            }
+        }
+        ring = next;
+    }
+
+    sea as f64 / (seen.len() - 1).max(1) as f64
+}
+
+// Rise from the sea to the highest of `cell` and its neighbors, per unit of
+// distance across the shore
+pub fn get_onshore_slope(
+    cell: usize,
+    heights: &[f64],
+    points: &[f64],
+    neighbors: &Graph,
+    sea_level: f64,
+) -> f64 {
+    let site = |c: usize| [points[c * 2], points[c * 2 + 1]];
+    let (mut top, mut spacing, mut count) = (heights[cell], 0., 0);
+    for &n in neighbors[cell].iter() {
+        top = top.max(heights[n]);
+        let (a, b) = (site(cell), site(n));
+        spacing += libm::hypot(b[0] - a[0], b[1] - a[1]);
+        count += 1;
+    }
+    if count == 0 || spacing == 0. {
+        return 0.;
+    }
+    (top - sea_level).max(0.) / (2. * spacing / count as f64)
+}
+
+// Kind of shore along each of `coast_lines`, from the land cell on its side.
+// Steep shores are cliffs or rocky, and gentle ones beaches where the sea is
+// open or marshes where it is sheltered.
+pub fn get_coast_kinds(
+    coast_lines: &[(usize, usize)],
+    heights: &[f64],
+    points: &[f64],
+    neighbors: &Graph,
+    voronoi_cells: &Graph,
+    sea_level: f64,
+) -> Vec<CoastKind> {
+    map_range(coast_lines.len(), |i| {
+        let (a, b) = coast_lines[i];
+        let land = voronoi_cells[a]
+            .iter()
+            .find(|&&c| heights[c] >= sea_level && voronoi_cells[b].contains(&c));
+        let cell = match land {
+            Some(&cell) => cell,
+            None => return CoastKind::Rocky,
+        };
+
+        let slope = get_onshore_slope(cell, heights, points, neighbors, sea_level);
+        if slope >= CLIFF_SLOPE {
+            CoastKind::Cliff
+        } else if slope >= ROCKY_SLOPE {
+            CoastKind::Rocky
+        } else if get_exposure(cell, heights, neighbors, sea_level) < MARSH_EXPOSURE {
+            CoastKind::Marsh
+        } else {
+            CoastKind::Beach
+        }
+    })
+}
+
+// Floods the most exposed soft coast cells, a pass at a time. Once the sea cuts
+// into a soft stretch the cells behind are sheltered and resist, so it leaves
+// bays between the harder headlands. Hardness follows the onshore slope, with
+// some noise so long uniform coasts break up too. Worlds from the same seed
+// erode the same way.
+pub fn erode_coasts(
+    heights: &mut [f64],
+    cell_heights: &mut [f64],
+    voronoi: &Voronoi,
+    sea_level: f64,
+    passes: usize,
+    seed: u64,
+) {
+    let points = &voronoi.delaunay.points;
+    let neighbors = &voronoi.delaunay.neighbors;
+    let voronoi_points = &voronoi.voronoi_points;
+    let voronoi_cells = &voronoi.voronoi_cells;
+    let noise = DetailNoise::seeded(seed, (cell_heights.len().max(1) as f64).sqrt() / 20.);
+
+    for _ in 0..passes {
+        // Every cell of a pass is judged against the coast it started with
+        let current = cell_heights.to_vec();
+        let coast = get_coast_cells(&current, neighbors, sea_level);
+        let eroded = coast
+            .into_iter()
+            .filter(|&cell| {
+                let slope = get_onshore_slope(cell, &current, points, neighbors, sea_level);
+                let grain = noise.get(points[cell * 2], points[cell * 2 + 1]);
+                let hardness = (slope / CLIFF_SLOPE + grain).clamp(0., 1.);
+                let exposure = get_exposure(cell, &current, neighbors, sea_level);
+                exposure * (1. - hardness) >= ERODIBLE
+            })
+            .collect::<Vec<_>>();
+        if eroded.is_empty() {
+            break;
+        }
+
+        let mut changed = Vec::new();
+        for &cell in eroded.iter() {
+            for &t in voronoi_points[cell].iter() {
+                if heights[t] > sea_level - ERODED_DEPTH {
+                    heights[t] = sea_level - ERODED_DEPTH;
+                    changed.push(t);
+                }
+            }
+        }
+
+        // Cells are the mean of their corners, as in `get_cell_heights`
+        for &t in changed.iter() {
+            for &cell in voronoi_cells[t].iter() {
+                let points = &voronoi_points[cell];
+                cell_heights[cell] =
+                    points.iter().map(|&n| heights[n]).sum::<f64>() / points.len() as f64;
+            }
+        }
+    }
+}
+
+// Most `levels` allowed, already 256 pieces per coast line
+pub const MAX_COAST_LEVELS: usize = 8;
+
+pub struct CoastDetailOptions {
+    pub seed: u64,
+    // Times each segment is halved, giving `2^levels` pieces per segment
+    pub levels: usize,
+    // Largest offset of a segment's midpoint, as a fraction of the room there
+    pub amplitude: f64,
+    // How much each level's offsets shrink from the one before
+    pub roughness: f64,
+}
+
+impl Default for CoastDetailOptions {
+    fn default() -> CoastDetailOptions {
+        CoastDetailOptions {
+            seed: 0,
+            levels: 4,
+            amplitude: 0.6,
+            roughness: 0.55,
+        }
+    }
+}
+
+impl CoastDetailOptions {
+    pub fn validate(&self) -> Result<(), TerrainError> {
+        if self.levels > MAX_COAST_LEVELS {
+            return Err(TerrainError::InvalidOption("levels"));
+        }
+        Ok(())
+    }
+}
+
+// A chain of coast lines, with land on its left, as a detailed polyline.
+// Segment `i`, from `coast_lines[lines[i]]`, is `points[i * samples..=(i + 1) * samples]`.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct CoastRing {
+    pub points: Vec<[f64; 2]>,
+    pub lines: Vec<usize>,
+    pub samples: usize,
+    // Rings run all the way round; the rest end at the edge of the map
+    pub closed: bool,
+}
+
+// Chains the coast lines into rings and displaces every segment with
+// midpoint subdivision. A segment lies between a land and a sea cell, and its
+// points stay inside the triangles it makes with their sites, so the detailed
+// coast never crosses into a third cell.
+pub fn get_coast_rings(
+    coast_lines: &[(usize, usize)],
+    heights: &[f64],
+    circumcenters: &[f64],
+    points: &[f64],
+    voronoi_cells: &Graph,
+    sea_level: f64,
+    options: &CoastDetailOptions,
+) -> Vec<CoastRing> {
+    let vertex = |t: usize| [circumcenters[t * 2], circumcenters[t * 2 + 1]];
+    let site = |c: usize| [points[c * 2], points[c * 2 + 1]];
+    let noise = DetailNoise::seeded(options.seed, (heights.len().max(1) as f64).sqrt());
+
+    // Each line with its land and sea sites, turned so the land is on the left
+    let lines = map_range(coast_lines.len(), |i| {
+        let (a, b) = coast_lines[i];
+        let cells = voronoi_cells[a]
+            .iter()
+            .cloned()
+            .filter(|c| voronoi_cells[b].contains(c))
+            .collect::<Vec<_>>();
+        let land = cells.iter().cloned().find(|&c| heights[c] >= sea_level);
+        let sea = cells.iter().cloned().find(|&c| heights[c] < sea_level);
+        match (land, sea) {
+            (Some(land), Some(sea)) => {
+                if cross(vertex(a), vertex(b), site(land)) < 0. {
+                    (b, a, Some((site(land), site(sea))))
+                } else {
+                    (a, b, Some((site(land), site(sea))))
+                }
+            }
+            _ => (a, b, None),
+        }
+    });
+
+    let mut outgoing: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut incoming = HashSet::new();
+    for (i, &(a, b, _)) in lines.iter().enumerate().rev() {
+        outgoing.entry(a).or_default().push(i);
+        incoming.insert(b);
     }
 
-    coast_lines
+    // Open chains start where nothing comes in, then whatever is left is closed
+    let mut starts = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !incoming.contains(&line.0))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    starts.extend(0..lines.len());
+
+    let samples = 1 << options.levels;
+    let mut used = vec![false; lines.len()];
+    let mut rings = Vec::new();
+    for start in starts {
+        if used[start] {
+            continue;
+        }
+
+        let mut ring = CoastRing {
+            points: vec![vertex(lines[start].0)],
+            lines: Vec::new(),
+            samples,
+            closed: false,
+        };
+        let mut next = Some(start);
+        while let Some(i) = next {
+            used[i] = true;
+            let (a, b, sites) = lines[i];
+            let segment = match sites {
+                Some(sites) => subdivide(vertex(a), vertex(b), sites, &noise, options),
+                None => straight(vertex(a), vertex(b), samples),
+            };
+            ring.points.extend_from_slice(&segment[1..]);
+            ring.lines.push(i);
+
+            next = outgoing
+                .get_mut(&b)
+                .and_then(|list| list.pop())
+                .filter(|&j| !used[j]);
+        }
+        ring.closed = lines[*ring.lines.last().unwrap()].1 == lines[start].0;
+        rings.push(ring);
+    }
+
+    rings
+}
+
+// Positive when `p` is left of the line from `a` to `b`
+fn cross(a: [f64; 2], b: [f64; 2], p: [f64; 2]) -> f64 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+fn straight(a: [f64; 2], b: [f64; 2], samples: usize) -> Vec<[f64; 2]> {
+    (0..=samples)
+        .map(|k| {
+            let t = k as f64 / samples as f64;
+            [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t]
+        })
+        .collect()
+}
+
+// Midpoint displacement from `a` to `b`, offsets to the left being towards the
+// land site and to the right towards the sea site
+fn subdivide(
+    a: [f64; 2],
+    b: [f64; 2],
+    (land, sea): ([f64; 2], [f64; 2]),
+    noise: &DetailNoise,
+    options: &CoastDetailOptions,
+) -> Vec<[f64; 2]> {
+    let length = libm::hypot(b[0] - a[0], b[1] - a[1]);
+    if length == 0. {
+        return straight(a, b, 1 << options.levels);
+    }
+    let along = [(b[0] - a[0]) / length, (b[1] - a[1]) / length];
+    let normal = [-along[1], along[0]];
+
+    // Room on either side at `t`, out to the edges of the triangle with that
+    // side's site. Near the edge of the map a site can be on the wrong side.
+    let triangle = |p: [f64; 2], side: f64| {
+        let d = [p[0] - a[0], p[1] - a[1]];
+        let apex = (d[0] * along[0] + d[1] * along[1]) / length;
+        let height = (d[0] * normal[0] + d[1] * normal[1]) * side;
+        (apex, height.max(0.))
+    };
+    let (land, sea) = (triangle(land, 1.), triangle(sea, -1.));
+    let room = |t: f64, (apex, height): (f64, f64)| {
+        let before = if apex > 0. { t / apex } else { f64::INFINITY };
+        let after = if apex < 1. {
+            (1. - t) / (1. - apex)
+        } else {
+            f64::INFINITY
+        };
+        height * before.min(after)
+    };
+
+    // (t, offset) pairs, refined a level at a time
+    let mut offsets = vec![(0., 0.), (1., 0.)];
+    let mut amplitude = options.amplitude;
+    let mut scale = 1.; // Finer levels read finer noise
+    for _ in 0..options.levels {
+        let mut refined = Vec::with_capacity(offsets.len() * 2 - 1);
+        for pair in offsets.windows(2) {
+            let ((t0, o0), (t1, o1)) = (pair[0], pair[1]);
+            let t = (t0 + t1) / 2.;
+            let base = (o0 + o1) / 2.;
+            let x = a[0] + along[0] * length * t + normal[0] * base;
+            let y = a[1] + along[1] * length * t + normal[1] * base;
+
+            let (left, right) = (room(t, land), room(t, sea));
+            let offset = base + noise.get(x * scale, y * scale) * amplitude * left.min(right);
+            refined.push((t0, o0));
+            refined.push((t, offset.clamp(-right * 0.9, left * 0.9)));
+        }
+        refined.push(*offsets.last().unwrap());
+        offsets = refined;
+        amplitude *= options.roughness;
+        scale *= 2.;
+    }
+
+    offsets
+        .into_iter()
+        .map(|(t, offset)| {
+            [
+                a[0] + along[0] * length * t + normal[0] * offset,
+                a[1] + along[1] * length * t + normal[1] * offset,
+            ]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain_generator::TerrainGenerator;
+
+    #[test]
+    fn coast_erosion_follows_the_seed() {
+        let radius = (500.0_f64 / 1024.).sqrt() / 10.0;
+        let world = TerrainGenerator::new(Some(7)).world(radius, 0.39).unwrap();
+        let eroded = |seed| {
+            let mut heights = world.heights.clone();
+            let mut cell_heights = world.cell_heights.clone();
+            erode_coasts(
+                &mut heights,
+                &mut cell_heights,
+                &world.voronoi,
+                world.sea_level,
+                3,
+                seed,
+            );
+            cell_heights
+        };
+
+        assert_ne!(eroded(1), world.cell_heights);
+        assert_eq!(eroded(1), eroded(1));
+        assert_ne!(eroded(1), eroded(2));
+    }
 }
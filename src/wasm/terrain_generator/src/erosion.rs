@@ -1,24 +1,33 @@
-pub fn get_flux(heights: &Vec<f64>, adjacent: &Vec<Vec<usize>>) -> Vec<f64> {
+use super::graph::Graph;
+use super::parallel::map_range;
+
+pub fn get_flux(heights: &Vec<f64>, adjacent: &Graph) -> Vec<f64> {
     let mut flux = vec![0.0; heights.len()];
 
     let mut sorted = (0..heights.len()).collect::<Vec<usize>>();
-    sorted.sort_unstable_by(|a, b| heights[*a].partial_cmp(&heights[*b]).unwrap().reverse());
+    sorted.sort_unstable_by(|a, b| {
+        let order = heights[*a].total_cmp(&heights[*b]).reverse();
+        order.then(a.cmp(b)) // Ties break on index, so flux doesn't depend on the sort
+    });
 
     // find downhill for each point.
     for &point in sorted.iter() {
+        if adjacent[point].len() <= 2 {
+            continue;
+        }
         let lowest_neighbour: usize = *adjacent[point]
             .iter()
-            .min_by(|a, b| heights[**a].partial_cmp(&heights[**b]).unwrap())
+            .min_by(|a, b| heights[**a].total_cmp(&heights[**b]))
             .unwrap();
 
-        if adjacent[point].len() > 2 && heights[lowest_neighbour] < heights[point] {
+        if heights[lowest_neighbour] < heights[point] {
             flux[lowest_neighbour] += flux[point] + 1.0;
         }
     }
     flux
 }
 
-pub fn fill_sinks(heights: Vec<f64>, adjacent: &Vec<Vec<usize>>, sea_level: f64) -> Vec<f64> {
+pub fn fill_sinks(heights: Vec<f64>, adjacent: &Graph, sea_level: f64) -> Vec<f64> {
     // Mewo implementation details: https://mewo2.com/notes/terrain/
     // Original paper: https://horizon.documentation.ird.fr/exl-doc/pleins_textes/pleins_textes_7/sous_copyright/010031925.pdf
     let epsilon = 1e-5;
@@ -36,7 +45,7 @@ pub fn fill_sinks(heights: Vec<f64>, adjacent: &Vec<Vec<usize>>, sea_level: f64)
         .collect();
 
     let mut sorted: Vec<(usize, f64)> = heights.clone().into_iter().enumerate().collect();
-    sorted.sort_unstable_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+    sorted.sort_unstable_by(|(i, a), (j, b)| a.total_cmp(b).then(i.cmp(j)));
 
     let mut changed = true;
     while changed {
@@ -92,27 +101,23 @@ pub fn plateau(points: &Vec<f64>, mut heights: Vec<f64>) -> Vec<f64> {
         let x = points[i * 2 + 0];
         let y = points[i * 2 + 1];
 
-        let distance_to_peak = ((x - peak_x).hypot(y - peak_y).min(0.5) / 0.5).powi(2);
+        let distance_to_peak = (libm::hypot(x - peak_x, y - peak_y).min(0.5) / 0.5).powi(2);
         heights[i] = (1. - distance_to_peak) * height + distance_to_peak * interpolate(height);
     }
 
     heights
 }
 
-pub fn erode(heights: Vec<f64>, adjacent: &Vec<Vec<usize>>, sea_level: f64) -> Vec<f64> {
+pub fn erode(heights: Vec<f64>, adjacent: &Graph, sea_level: f64, erosion_rate: f64) -> Vec<f64> {
     // First, smooth out the landscape a bit, and fill sinks
     let heights = smooth(heights, adjacent);
     let heights = fill_sinks(heights, adjacent, sea_level);
 
     let flux = get_flux(&heights, adjacent);
-    let adjacent = adjacent
-        .iter()
-        .map(|arr| arr.iter().map(|n| heights[*n]).collect::<Vec<f64>>())
-        .collect::<Vec<Vec<f64>>>();
 
-    let erosion_rate = 0.015;
-    let erosion = |(i, height): (usize, f64)| {
-        let point_flux = (flux[i] + 1.).ln();
+    let erosion = |i: usize| {
+        let height = heights[i];
+        let point_flux = libm::log(flux[i] + 1.);
 
         let erosion = point_flux * erosion_rate * height;
 
@@ -120,7 +125,7 @@ pub fn erode(heights: Vec<f64>, adjacent: &Vec<Vec<usize>>, sea_level: f64) -> V
             // Find lowest neighbor.
             let low = adjacent[i]
                 .iter()
-                .cloned()
+                .map(|&n| heights[n])
                 .fold(0. / 0., f64::min)
                 .min(height);
 
@@ -134,16 +139,14 @@ pub fn erode(heights: Vec<f64>, adjacent: &Vec<Vec<usize>>, sea_level: f64) -> V
         }
     };
 
-    let heights = heights
-        .into_iter()
-        .enumerate()
-        .map(erosion)
-        .collect::<Vec<f64>>();
+    let heights = map_range(heights.len(), erosion);
 
     heights
 }
 
-pub fn smooth(mut heights: Vec<f64>, adjacent: &Vec<Vec<usize>>) -> Vec<f64> {
+// Updates heights in place, in index order, so it stays serial even with the
+// `parallel` feature: splitting it up would change the result.
+pub fn smooth(mut heights: Vec<f64>, adjacent: &Graph) -> Vec<f64> {
     let alpha = 1.;
     let alpha = 0.66;
 
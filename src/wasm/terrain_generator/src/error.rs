@@ -0,0 +1,45 @@
+use std::fmt;
+use wasm_bindgen::JsValue;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TerrainError {
+    // An option that can't produce a world, by name
+    InvalidOption(&'static str),
+    // The sampled points are too few or all collinear
+    Triangulation,
+    // Two sampled points landed on the same spot, by point index
+    CoincidentPoint(usize),
+    // Noise or erosion produced NaN or infinity, by vertex index
+    NonFiniteHeight(usize),
+    // A saved world that can't be read
+    InvalidWorld(String),
+}
+
+impl fmt::Display for TerrainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TerrainError::InvalidOption(name) => write!(f, "Invalid option `{}`", name),
+            TerrainError::Triangulation => write!(f, "Points could not be triangulated"),
+            TerrainError::CoincidentPoint(i) => write!(f, "Point {} coincides with another", i),
+            TerrainError::NonFiniteHeight(i) => write!(f, "Height of vertex {} is not finite", i),
+            TerrainError::InvalidWorld(reason) => write!(f, "Invalid world: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for TerrainError {}
+
+// Thrown as a JS `Error`, so callers can `catch` it
+impl From<TerrainError> for JsValue {
+    fn from(error: TerrainError) -> JsValue {
+        js_sys::Error::new(&error.to_string()).into()
+    }
+}
+
+// Finds the first NaN or infinite height
+pub fn check_heights(heights: &[f64]) -> Result<(), TerrainError> {
+    match heights.iter().position(|h| !h.is_finite()) {
+        Some(i) => Err(TerrainError::NonFiniteHeight(i)),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,259 @@
+use super::climate::Biome;
+use super::coasts::CoastKind;
+use super::mouths::{Mouth, MouthKind};
+use super::resources::{DepositKind, Resources};
+use super::river_network::RiverNetwork;
+use super::terrain_generator::World;
+
+// Spec: https://tools.ietf.org/html/rfc7946
+// Coordinates are emitted as-is, in the unit square the generator samples in.
+
+#[derive(Serialize, Debug)]
+pub struct FeatureCollection {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    features: Vec<Feature>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Feature {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    geometry: Geometry,
+    properties: Properties,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", content = "coordinates")]
+pub enum Geometry {
+    Point([f64; 2]),
+    Polygon(Vec<Vec<[f64; 2]>>),
+    LineString(Vec<[f64; 2]>),
+    MultiLineString(Vec<Vec<[f64; 2]>>),
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Properties {
+    Cell {
+        index: usize,
+        height: f64,
+        biome: Biome,
+        temperature: f64,
+        precipitation: f64,
+        fertility: f64,
+        forest: f64,
+    },
+    River {
+        width: f64,
+        flux: Vec<f64>,
+        strahler: u32,
+        shreve: u32,
+        discharge: f64,
+    },
+    // `river` is the index of the river feature
+    Mouth {
+        mouth: MouthKind,
+        river: usize,
+    },
+    Distributary {
+        river: usize,
+    },
+    Coast {
+        shore: CoastKind,
+    },
+    // At the site of the cell it's in
+    Deposit {
+        resource: DepositKind,
+        cell: usize,
+        richness: f64,
+    },
+}
+
+impl FeatureCollection {
+    pub fn new(world: &World) -> FeatureCollection {
+        let circumcenters = &world.voronoi.circumcenters;
+        let mut features = get_cells(world);
+        features.extend(get_rivers(
+            circumcenters,
+            &world.rivers,
+            &world.river_network,
+        ));
+        features.extend(get_mouths(circumcenters, &world.mouths));
+        features.extend(get_coasts(
+            circumcenters,
+            &world.coast_lines,
+            &world.coast_kinds,
+        ));
+        features.extend(get_deposits(
+            &world.voronoi.delaunay.points,
+            &world.resources,
+        ));
+
+        FeatureCollection {
+            kind: "FeatureCollection",
+            features,
+        }
+    }
+}
+
+impl Feature {
+    fn new(geometry: Geometry, properties: Properties) -> Feature {
+        Feature {
+            kind: "Feature",
+            geometry,
+            properties,
+        }
+    }
+}
+
+fn vertex(circumcenters: &[f64], t: usize) -> [f64; 2] {
+    [circumcenters[t * 2], circumcenters[t * 2 + 1]]
+}
+
+// Cells on the hull only have corners on their inner side, so their rings
+// run back through the site to close around the part of the cell inside the map.
+fn get_cells(world: &World) -> Vec<Feature> {
+    let voronoi = &world.voronoi;
+    let climate = &world.climate;
+    let mut on_hull = vec![false; world.cell_heights.len()];
+    for &i in voronoi.delaunay.hull.iter() {
+        on_hull[i] = true;
+    }
+    let mut cells = Vec::new();
+
+    for (i, points) in voronoi.voronoi_points.iter().enumerate() {
+        if points.len() < 2 {
+            continue; // Not enough vertices to make a ring
+        }
+
+        let mut ring: Vec<[f64; 2]> = points
+            .iter()
+            .map(|&t| vertex(&voronoi.circumcenters, t))
+            .collect();
+        if on_hull[i] {
+            ring.push(vertex(&voronoi.delaunay.points, i));
+        }
+        if ring.len() < 3 {
+            continue;
+        }
+        ring.push(ring[0]); // Linear rings are closed
+
+        cells.push(Feature::new(
+            Geometry::Polygon(vec![ring]),
+            Properties::Cell {
+                index: i,
+                height: world.cell_heights[i],
+                biome: climate.biomes[i],
+                temperature: climate.temperatures[i],
+                precipitation: climate.precipitation[i],
+                fertility: world.resources.fertility[i],
+                forest: world.vegetation.forest[i],
+            },
+        ));
+    }
+
+    cells
+}
+
+fn get_rivers(
+    circumcenters: &[f64],
+    rivers: &[Vec<(usize, f64)>],
+    network: &RiverNetwork,
+) -> Vec<Feature> {
+    rivers
+        .iter()
+        .zip(network.streams.iter())
+        .map(|(river, stream)| {
+            let line = river
+                .iter()
+                .map(|&(t, _)| vertex(circumcenters, t))
+                .collect();
+            let flux: Vec<f64> = river.iter().map(|&(_, flux)| flux).collect();
+            let width = flux.iter().cloned().fold(0., f64::max);
+
+            // Streams run upstream, so their first node is where they end, at
+            // the sea, a sink or a confluence. Order and discharge are largest there.
+            let properties = Properties::River {
+                width,
+                flux,
+                strahler: stream.strahler.first().cloned().unwrap_or(0),
+                shreve: stream.shreve.first().cloned().unwrap_or(0),
+                discharge: stream.discharge.first().cloned().unwrap_or(0.),
+            };
+            Feature::new(Geometry::LineString(line), properties)
+        })
+        .collect()
+}
+
+fn get_mouths(circumcenters: &[f64], mouths: &[Mouth]) -> Vec<Feature> {
+    let mut features = Vec::new();
+
+    for mouth in mouths.iter() {
+        features.push(Feature::new(
+            Geometry::Point(vertex(circumcenters, mouth.apex)),
+            Properties::Mouth {
+                mouth: mouth.kind,
+                river: mouth.stream,
+            },
+        ));
+        for channel in mouth.distributaries.iter() {
+            let line = channel.iter().map(|&t| vertex(circumcenters, t)).collect();
+            features.push(Feature::new(
+                Geometry::LineString(line),
+                Properties::Distributary {
+                    river: mouth.stream,
+                },
+            ));
+        }
+    }
+
+    features
+}
+
+// One feature per kind of shore
+fn get_coasts(
+    circumcenters: &[f64],
+    coast_lines: &[(usize, usize)],
+    coast_kinds: &[CoastKind],
+) -> Vec<Feature> {
+    let kinds = [
+        CoastKind::Beach,
+        CoastKind::Cliff,
+        CoastKind::Marsh,
+        CoastKind::Rocky,
+    ];
+
+    kinds
+        .iter()
+        .map(|&kind| {
+            let lines = coast_lines
+                .iter()
+                .zip(coast_kinds.iter())
+                .filter(|&(_, &k)| k == kind)
+                .map(|(&(a, b), _)| vec![vertex(circumcenters, a), vertex(circumcenters, b)])
+                .collect();
+            Feature::new(
+                Geometry::MultiLineString(lines),
+                Properties::Coast { shore: kind },
+            )
+        })
+        .collect()
+}
+
+fn get_deposits(points: &[f64], resources: &Resources) -> Vec<Feature> {
+    resources
+        .deposits
+        .iter()
+        .map(|deposit| {
+            Feature::new(
+                Geometry::Point(vertex(points, deposit.cell)),
+                Properties::Deposit {
+                    resource: deposit.kind,
+                    cell: deposit.cell,
+                    richness: deposit.richness,
+                },
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,175 @@
+use super::voronoi::Voronoi;
+use std::collections::HashSet;
+
+// How glaciers carve the land in `erode_glaciers`
+pub struct GlacierOptions {
+    // Deepest a valley floor is cut, under the most ice
+    pub depth: f64,
+    // Ice, in nodes feeding a glacier, at which it cuts half as deep as it can
+    pub half_depth_ice: f64,
+    // Rings of nodes either side of the floor widened into valley walls, for
+    // the smallest and largest glaciers
+    pub min_width: usize,
+    pub max_width: usize,
+    // Height of the moraine left at a glacier's end, for the largest glaciers
+    pub moraine: f64,
+}
+
+impl Default for GlacierOptions {
+    fn default() -> GlacierOptions {
+        GlacierOptions {
+            depth: 0.04,
+            half_depth_ice: 20.,
+            min_width: 1,
+            max_width: 3,
+            moraine: 0.008,
+        }
+    }
+}
+
+// Ice gathers where the warmest month still freezes and flows down the steepest
+// way, melting as it goes lower, until it's gone or reaches the sea. Along the
+// way it cuts a flat floor with steep walls, wider and deeper the more ice,
+// into a U-shaped valley, and drops what it scraped off as a moraine where it
+// melts.
+//
+// `summer_temperatures` are per cell, and a node is under ice when its cells
+// are on average. Land stays above the sea, so coast lines don't move.
+pub fn erode_glaciers(
+    heights: &mut [f64],
+    cell_heights: &mut [f64],
+    voronoi: &Voronoi,
+    summer_temperatures: &[f64],
+    sea_level: f64,
+    options: &GlacierOptions,
+) {
+    let adjacent = &voronoi.adjacent;
+    let voronoi_points = &voronoi.voronoi_points;
+    let voronoi_cells = &voronoi.voronoi_cells;
+    let nodes = heights.len();
+    let original = heights.to_vec();
+    let summer = |t: usize| {
+        let cells = &voronoi_cells[t];
+        cells.iter().map(|&c| summer_temperatures[c]).sum::<f64>() / cells.len() as f64
+    };
+
+    let mut sorted = (0..nodes).collect::<Vec<_>>();
+    sorted.sort_unstable_by(|&a, &b| original[b].total_cmp(&original[a]).then(a.cmp(&b)));
+
+    // Ice flowing into each node, from the highest down
+    let mut ice = vec![0.; nodes];
+    let mut termini = Vec::new();
+    for &t in sorted.iter() {
+        if original[t] < sea_level {
+            continue;
+        }
+        let temperature = summer(t);
+        let gained = if temperature < 0. {
+            1.
+        } else {
+            -(1. + temperature)
+        };
+        if ice[t] <= 0. && gained <= 0. {
+            continue;
+        }
+        let left = ice[t] + gained;
+
+        let lowest = adjacent[t]
+            .iter()
+            .cloned()
+            .min_by(|&a, &b| original[a].total_cmp(&original[b]).then(a.cmp(&b)));
+        match lowest {
+            Some(n) if left > 0. && original[n] < original[t] => {
+                if original[n] >= sea_level {
+                    ice[n] += left;
+                }
+            }
+            // Melted away, or stuck in a hollow
+            _ => termini.push(t),
+        }
+        ice[t] = ice[t].max(gained);
+    }
+    let most = ice.iter().cloned().fold(0., f64::max);
+    if most <= 0. {
+        return;
+    }
+
+    let floor_height = |h: f64| h.max(sea_level + f64::EPSILON);
+    let mut changed = HashSet::new();
+    let mut lower = |n: usize, height: f64, heights: &mut [f64]| {
+        if height < heights[n] {
+            heights[n] = height;
+            changed.insert(n);
+        }
+    };
+    for &t in sorted.iter().filter(|&&t| ice[t] > 0.) {
+        let share = ice[t] / (ice[t] + options.half_depth_ice);
+        let floor = floor_height(original[t] - options.depth * share);
+        lower(t, floor, heights);
+
+        // Walls rise steeply from the floor, out to the valley's edge
+        let width = options.min_width
+            + ((options.max_width - options.min_width) as f64 * (ice[t] / most).sqrt()).round()
+                as usize;
+        let mut seen = HashSet::new();
+        seen.insert(t);
+        let mut ring = vec![t];
+        for k in 1..=width {
+            let mut next = Vec::new();
+            for &a in ring.iter() {
+                for &n in adjacent[a].iter() {
+                    if !seen.insert(n) || original[n] < sea_level {
+                        continue;
+                    }
+                    let u = k as f64 / (width + 1) as f64;
+                    let wall = floor + (original[n] - floor).max(0.) * u * u;
+                    lower(n, floor_height(wall), heights);
+                    next.push(n);
+                }
+            }
+            ring = next;
+        }
+    }
+
+    // Moraines, around where the ice ends
+    for &t in termini.iter() {
+        let size = options.moraine * (ice[t] / most).sqrt();
+        for &n in adjacent[t].iter().chain(std::iter::once(&t)) {
+            let height = original[n] + if n == t { size } else { size / 2. };
+            if original[n] >= sea_level && heights[n] < height {
+                heights[n] = height;
+                changed.insert(n);
+            }
+        }
+    }
+
+    // Cells are the mean of their corners, as in `get_cell_heights`. Cells that
+    // would cross the sea level get their old corners back, until none do.
+    let mut changed = changed.into_iter().collect::<Vec<_>>();
+    changed.sort_unstable();
+    let mut cells = changed
+        .iter()
+        .flat_map(|&t| voronoi_cells[t].iter().cloned())
+        .collect::<Vec<_>>();
+    cells.sort_unstable();
+    cells.dedup();
+    let mean = |cell: usize, heights: &[f64]| {
+        let points = &voronoi_points[cell];
+        points.iter().map(|&n| heights[n]).sum::<f64>() / points.len() as f64
+    };
+    let mut crossed = true;
+    while crossed {
+        crossed = false;
+        for &cell in cells.iter() {
+            if (cell_heights[cell] >= sea_level) != (mean(cell, heights) >= sea_level) {
+                for &n in voronoi_points[cell].iter() {
+                    heights[n] = original[n];
+                }
+                crossed = true;
+            }
+        }
+    }
+    for &cell in cells.iter() {
+        cell_heights[cell] = mean(cell, heights);
+    }
+}
@@ -0,0 +1,160 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::ops::Index;
+
+// Compressed sparse row adjacency: the neighbors of node `i` are
+// `values[offsets[i]..offsets[i + 1]]`. One allocation for every list instead
+// of one per node, and indexing hands out plain slices.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Graph {
+    offsets: Vec<usize>,
+    values: Vec<usize>,
+}
+
+impl Graph {
+    pub fn with_capacity(nodes: usize, values: usize) -> Graph {
+        let mut offsets = Vec::with_capacity(nodes + 1);
+        offsets.push(0);
+        Graph {
+            offsets,
+            values: Vec::with_capacity(values),
+        }
+    }
+
+    pub fn from_lists(lists: &[Vec<usize>]) -> Graph {
+        let mut graph = Graph::with_capacity(lists.len(), lists.iter().map(Vec::len).sum());
+        for list in lists.iter() {
+            graph.push(list.iter().cloned());
+        }
+        graph
+    }
+
+    // Appends the next node, with `list` as its neighbors.
+    pub fn push<I: IntoIterator<Item = usize>>(&mut self, list: I) {
+        self.values.extend(list);
+        self.offsets.push(self.values.len());
+    }
+
+    // Number of nodes
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &[usize]> {
+        self.offsets.windows(2).map(move |w| &self.values[w[0]..w[1]])
+    }
+
+    pub fn offsets(&self) -> &Vec<usize> {
+        &self.offsets
+    }
+
+    pub fn values(&self) -> &Vec<usize> {
+        &self.values
+    }
+
+    // Distance from every node to its nearest source, and which source that is,
+    // walking edges between `position`s. Unreachable nodes are infinitely far.
+    pub fn nearest(
+        &self,
+        sources: &[usize],
+        position: &dyn Fn(usize) -> [f64; 2],
+    ) -> Vec<(f64, usize)> {
+        let mut nearest = vec![(f64::INFINITY, usize::MAX); self.len()];
+        let mut queue = BinaryHeap::new();
+        for &s in sources.iter() {
+            nearest[s] = (0., s);
+            queue.push(Nearest(0., s));
+        }
+
+        while let Some(Nearest(distance, i)) = queue.pop() {
+            if distance > nearest[i].0 {
+                continue;
+            }
+            let a = position(i);
+            for &n in self[i].iter() {
+                let b = position(n);
+                let next = distance + libm::hypot(b[0] - a[0], b[1] - a[1]);
+                if next < nearest[n].0 {
+                    nearest[n] = (next, nearest[i].1);
+                    queue.push(Nearest(next, n));
+                }
+            }
+        }
+
+        nearest
+    }
+}
+
+// An entry for Dijkstra's queue, nearest first
+#[derive(PartialEq)]
+struct Nearest(f64, usize);
+
+impl Eq for Nearest {}
+
+impl Ord for Nearest {
+    fn cmp(&self, other: &Nearest) -> Ordering {
+        other.0.total_cmp(&self.0).then(other.1.cmp(&self.1))
+    }
+}
+
+impl PartialOrd for Nearest {
+    fn partial_cmp(&self, other: &Nearest) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Index<usize> for Graph {
+    type Output = [usize];
+
+    fn index(&self, i: usize) -> &[usize] {
+        &self.values[self.offsets[i]..self.offsets[i + 1]]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_lists_round_trips() {
+        let lists = vec![vec![1, 2], vec![], vec![0], vec![], vec![]];
+        let graph = Graph::from_lists(&lists);
+        assert_eq!(graph.len(), 5);
+        assert_eq!(graph.offsets(), &vec![0, 2, 2, 3, 3, 3]);
+        assert_eq!(graph.values(), &vec![1, 2, 0]);
+        assert_eq!(
+            graph.iter().map(<[usize]>::to_vec).collect::<Vec<_>>(),
+            lists
+        );
+    }
+
+    #[test]
+    fn empty_rows() {
+        let graph = Graph::from_lists(&[vec![], vec![], vec![3]]);
+        assert!(graph[0].is_empty());
+        assert!(graph[1].is_empty());
+        assert_eq!(&graph[2], &[3]);
+
+        let empty = Graph::from_lists(&[]);
+        assert_eq!(empty.len(), 0);
+        assert_eq!(empty.iter().count(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn neighbors_past_the_last_node() {
+        let graph = Graph::from_lists(&[vec![1], vec![0]]);
+        let _ = &graph[2];
+    }
+
+    #[test]
+    fn nearest_walks_edges() {
+        // A path 0 - 1 - 2 along the x axis, and 3 on its own
+        let graph = Graph::from_lists(&[vec![1], vec![0, 2], vec![1], vec![]]);
+        let position = |i: usize| [i as f64, 0.];
+        let nearest = graph.nearest(&[0], &position);
+        assert_eq!(nearest[0], (0., 0));
+        assert_eq!(nearest[2], (2., 0));
+        assert_eq!(nearest[3].0, f64::INFINITY);
+    }
+}
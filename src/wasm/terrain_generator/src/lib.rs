@@ -1,3 +1,23 @@
+//! Noise and voronoi-based terrain generation.
+//!
+//! # Determinism
+//!
+//! A seed and a set of `WorldOptions` always produce the same `World`, bit for
+//! bit, on every platform and with or without the `parallel` feature:
+//!
+//! - All randomness comes from the seeded `bracket_random` generator, drawn in
+//!   a fixed order.
+//! - Transcendental functions go through `libm` rather than the platform's
+//!   math library, whose results may differ in the last bit. Noise is `f32`
+//!   but only uses basic arithmetic, which IEEE 754 rounds the same everywhere.
+//! - Sorts that can see equal keys break ties on index.
+//! - Parallel stages only compute values per node; anything that accumulates
+//!   runs serially in a fixed order.
+//!
+//! `World::fingerprint` hashes the saved world, and `tests/determinism.rs`
+//! checks it against `tests/golden.txt`. Changes that are meant to alter
+//! output should update the golden file in the same commit.
+
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global allocator.
 #[cfg(feature = "wee_alloc")]
 #[global_allocator]
@@ -7,11 +27,27 @@ static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 #[macro_use]
 extern crate serde_derive;
 
+mod bathymetry;
+mod binary;
+mod climate;
 mod coasts;
 mod erosion;
+pub mod error;
+mod geojson;
+mod glaciers;
+mod graph;
+pub mod mesh;
+pub mod mouths;
 mod noise;
+mod parallel;
 mod poisson;
+mod resources;
+pub mod river_network;
 mod rivers;
+pub mod stats;
+pub mod svg;
 pub mod terrain_generator;
+mod typed_arrays;
 mod utils;
+mod vegetation;
 mod voronoi;
@@ -0,0 +1,344 @@
+use super::voronoi::Voronoi;
+use serde_json::json;
+use std::fmt::Write;
+
+// The terrain surface is the same triangle fan the WebGL renderer draws: each of
+// `voronoi_triangles` joins a cell center with two of its circumcenters. Vertices
+// are laid out as all cell centers first, then all circumcenters.
+//
+// Both exporters use a right-handed, Y-up frame: map `x` stays `x`, height becomes
+// `y` and map `y` becomes `-z`.
+
+#[derive(Clone, Debug)]
+pub struct MeshOptions {
+    pub exaggeration: f64,
+    pub sea_plane: bool,
+    pub hill_color: [f32; 3],
+    pub land_color: [f32; 3],
+    pub water_color: [f32; 3],
+    pub depth_color: [f32; 3],
+    pub ice_color: [f32; 3],
+}
+
+impl Default for MeshOptions {
+    fn default() -> MeshOptions {
+        MeshOptions {
+            exaggeration: 0.3, // Same as `zScale` in `webgl/draw.js`
+            sea_plane: true,
+            hill_color: [0.91, 1.0, 0.8],
+            land_color: [0.2, 0.54, 0.24],
+            water_color: [0.07, 0.67, 0.78],
+            depth_color: [0.02, 0.1, 0.18],
+            ice_color: [0.93, 0.96, 1.0],
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Mesh {
+    name: &'static str,
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    colors: Vec<[f32; 3]>,
+    indices: Vec<u32>,
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let length = (a[0] * a[0] + a[1] * a[1] + a[2] * a[2]).sqrt();
+    if length == 0. {
+        return [0., 1., 0.];
+    }
+    [a[0] / length, a[1] / length, a[2] / length]
+}
+
+fn mix(a: [f32; 3], b: [f32; 3], t: f64) -> [f32; 3] {
+    let t = t.clamp(0., 1.) as f32;
+    [
+        a[0] * t + b[0] * (1. - t),
+        a[1] * t + b[1] * (1. - t),
+        a[2] * t + b[2] * (1. - t),
+    ]
+}
+
+impl Mesh {
+    pub fn terrain(
+        voronoi: &Voronoi,
+        heights: &[f64],
+        cell_heights: &[f64],
+        glaciers: &[bool],
+        sea_level: f64,
+        options: &MeshOptions,
+    ) -> Mesh {
+        let points = &voronoi.delaunay.points;
+        let circumcenters = &voronoi.circumcenters;
+        let offset = cell_heights.len();
+
+        let elevation = |h: f64| ((h - sea_level) * options.exaggeration + sea_level) as f32;
+        let color = |h: f64| {
+            if h >= sea_level {
+                let t = (h - sea_level) / (1. - sea_level);
+                mix(options.hill_color, options.land_color, t)
+            } else {
+                mix(options.water_color, options.depth_color, h / sea_level)
+            }
+        };
+
+        let mut positions = Vec::with_capacity(offset + heights.len());
+        let mut colors = Vec::with_capacity(offset + heights.len());
+        for (i, &h) in cell_heights.iter().enumerate() {
+            positions.push([points[i * 2] as f32, elevation(h), -points[i * 2 + 1] as f32]);
+            colors.push(if glaciers[i] {
+                options.ice_color
+            } else {
+                color(h)
+            });
+        }
+        // Corners are iced when most of their cells are
+        for (t, &h) in heights.iter().enumerate() {
+            let (x, y) = (circumcenters[t * 2], circumcenters[t * 2 + 1]);
+            positions.push([x as f32, elevation(h), -y as f32]);
+            let cells = &voronoi.voronoi_cells[t];
+            let iced = cells.iter().filter(|&&c| glaciers[c]).count();
+            colors.push(if iced * 2 > cells.len() {
+                options.ice_color
+            } else {
+                color(h)
+            });
+        }
+
+        let mut indices = Vec::with_capacity(voronoi.voronoi_triangles.len());
+        let mut normals = vec![[0.; 3]; positions.len()];
+        for triangle in voronoi.voronoi_triangles.chunks_exact(3) {
+            if triangle[1] >= heights.len() || triangle[2] >= heights.len() {
+                continue; // Hull cells close their fan with `EMPTY / 3`
+            }
+            let a = triangle[0];
+            let mut b = offset + triangle[1];
+            let mut c = offset + triangle[2];
+            if b == c {
+                continue;
+            }
+
+            let mut normal = cross(
+                sub(positions[b], positions[a]),
+                sub(positions[c], positions[a]),
+            );
+            if normal[1] < 0. {
+                // Keep counter-clockwise winding when seen from above
+                std::mem::swap(&mut b, &mut c);
+                normal = [-normal[0], -normal[1], -normal[2]];
+            }
+
+            // Area weighted, so small slivers don't skew shared normals
+            for &v in [a, b, c].iter() {
+                normals[v] = [
+                    normals[v][0] + normal[0],
+                    normals[v][1] + normal[1],
+                    normals[v][2] + normal[2],
+                ];
+            }
+            indices.extend([a as u32, b as u32, c as u32].iter());
+        }
+        let normals = normals.into_iter().map(normalize).collect();
+
+        Mesh {
+            name: "terrain",
+            positions,
+            normals,
+            colors,
+            indices,
+        }
+    }
+
+    pub fn sea_plane(sea_level: f64, options: &MeshOptions) -> Mesh {
+        // Covers the jittered borders `poisson::add_borders` places outside the unit square
+        let (min, max) = (-0.1, 1.1);
+        let y = sea_level as f32;
+
+        Mesh {
+            name: "sea",
+            positions: vec![[min, y, -min], [max, y, -min], [max, y, -max], [min, y, -max]],
+            normals: vec![[0., 1., 0.]; 4],
+            colors: vec![options.water_color; 4],
+            indices: vec![0, 1, 2, 0, 2, 3],
+        }
+    }
+}
+
+pub fn build(
+    voronoi: &Voronoi,
+    heights: &[f64],
+    cell_heights: &[f64],
+    glaciers: &[bool],
+    sea_level: f64,
+    options: &MeshOptions,
+) -> Vec<Mesh> {
+    let mut meshes = vec![Mesh::terrain(
+        voronoi,
+        heights,
+        cell_heights,
+        glaciers,
+        sea_level,
+        options,
+    )];
+    if options.sea_plane {
+        meshes.push(Mesh::sea_plane(sea_level, options));
+    }
+    meshes
+}
+
+// Spec: http://paulbourke.net/dataformats/obj/
+// Vertex colors follow the common `v x y z r g b` extension Blender reads.
+pub fn to_obj(meshes: &[Mesh]) -> String {
+    let mut obj = String::new();
+    let mut base = 1; // OBJ indices are 1-based and global across objects
+
+    for mesh in meshes.iter() {
+        writeln!(obj, "o {}", mesh.name).unwrap();
+        for (p, c) in mesh.positions.iter().zip(mesh.colors.iter()) {
+            writeln!(
+                obj,
+                "v {} {} {} {:.4} {:.4} {:.4}",
+                p[0], p[1], p[2], c[0], c[1], c[2]
+            )
+            .unwrap();
+        }
+        for n in mesh.normals.iter() {
+            writeln!(obj, "vn {:.5} {:.5} {:.5}", n[0], n[1], n[2]).unwrap();
+        }
+        for face in mesh.indices.chunks_exact(3) {
+            let (a, b, c) = (face[0] + base, face[1] + base, face[2] + base);
+            writeln!(obj, "f {0}//{0} {1}//{1} {2}//{2}", a, b, c).unwrap();
+        }
+        base += mesh.positions.len() as u32;
+    }
+
+    obj
+}
+
+// Spec: https://github.com/KhronosGroup/glTF/tree/master/specification/2.0
+pub fn to_glb(meshes: &[Mesh]) -> Vec<u8> {
+    const ARRAY_BUFFER: u32 = 34962;
+    const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+    const FLOAT: u32 = 5126;
+    const UNSIGNED_INT: u32 = 5125;
+
+    let mut bin: Vec<u8> = Vec::new();
+    let mut buffer_views = Vec::new();
+    let mut accessors = Vec::new();
+    let mut gltf_meshes = Vec::new();
+    let mut nodes = Vec::new();
+
+    let mut push_view = |bin: &mut Vec<u8>, bytes: Vec<u8>, target: u32| {
+        let view = json!({
+            "buffer": 0,
+            "byteOffset": bin.len(),
+            "byteLength": bytes.len(),
+            "target": target,
+        });
+        bin.extend(bytes);
+        buffer_views.push(view);
+        buffer_views.len() - 1
+    };
+
+    let vec3_bytes = |values: &[[f32; 3]]| {
+        values
+            .iter()
+            .flat_map(|v| v.iter().flat_map(|f| f.to_le_bytes().to_vec()))
+            .collect::<Vec<u8>>()
+    };
+
+    for mesh in meshes.iter() {
+        let mut min = [f32::INFINITY; 3];
+        let mut max = [f32::NEG_INFINITY; 3];
+        for p in mesh.positions.iter() {
+            for k in 0..3 {
+                min[k] = min[k].min(p[k]);
+                max[k] = max[k].max(p[k]);
+            }
+        }
+
+        let count = mesh.positions.len();
+        let mut attribute = |bin: &mut Vec<u8>, values: &[[f32; 3]], bounds: bool| {
+            let view = push_view(bin, vec3_bytes(values), ARRAY_BUFFER);
+            let mut accessor = json!({
+                "bufferView": view,
+                "componentType": FLOAT,
+                "count": count,
+                "type": "VEC3",
+            });
+            if bounds {
+                accessor["min"] = json!(min);
+                accessor["max"] = json!(max);
+            }
+            accessors.push(accessor);
+            accessors.len() - 1
+        };
+        let position = attribute(&mut bin, &mesh.positions, true);
+        let normal = attribute(&mut bin, &mesh.normals, false);
+        let color = attribute(&mut bin, &mesh.colors, false);
+
+        let indices = mesh.indices.iter().flat_map(|i| i.to_le_bytes().to_vec()).collect();
+        let view = push_view(&mut bin, indices, ELEMENT_ARRAY_BUFFER);
+        accessors.push(json!({
+            "bufferView": view,
+            "componentType": UNSIGNED_INT,
+            "count": mesh.indices.len(),
+            "type": "SCALAR",
+        }));
+
+        gltf_meshes.push(json!({
+            "name": mesh.name,
+            "primitives": [{
+                "attributes": { "POSITION": position, "NORMAL": normal, "COLOR_0": color },
+                "indices": accessors.len() - 1,
+            }],
+        }));
+        nodes.push(json!({ "name": mesh.name, "mesh": gltf_meshes.len() - 1 }));
+    }
+
+    let gltf = json!({
+        "asset": { "version": "2.0", "generator": "terrain_generator" },
+        "scene": 0,
+        "scenes": [{ "nodes": (0..nodes.len()).collect::<Vec<_>>() }],
+        "nodes": nodes,
+        "meshes": gltf_meshes,
+        "accessors": accessors,
+        "bufferViews": buffer_views,
+        "buffers": [{ "byteLength": bin.len() }],
+    });
+
+    // Chunks are 4-byte aligned: JSON is padded with spaces, BIN with zeros
+    let mut json = serde_json::to_vec(&gltf).unwrap();
+    while json.len() % 4 != 0 {
+        json.push(b' ');
+    }
+    while bin.len() % 4 != 0 {
+        bin.push(0);
+    }
+
+    let length = 12 + 8 + json.len() + 8 + bin.len();
+    let mut glb = Vec::with_capacity(length);
+    glb.extend(b"glTF");
+    glb.extend(&2u32.to_le_bytes());
+    glb.extend(&(length as u32).to_le_bytes());
+    glb.extend(&(json.len() as u32).to_le_bytes());
+    glb.extend(b"JSON");
+    glb.extend(json);
+    glb.extend(&(bin.len() as u32).to_le_bytes());
+    glb.extend(b"BIN\0");
+    glb.extend(bin);
+    glb
+}
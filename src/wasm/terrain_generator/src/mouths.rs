@@ -0,0 +1,335 @@
+use super::graph::Graph;
+use super::river_network::RiverNetwork;
+use super::voronoi::Voronoi;
+use std::collections::HashSet;
+
+// Nodes above the sea reach measured for a river's slope
+const RIVER_REACH: usize = 8;
+// Rings of sea nodes around the apex measured for the coast's slope
+const COAST_RINGS: usize = 3;
+// A delta channel splits every this many nodes
+const SPLIT_EVERY: usize = 2;
+
+// Which river mouths `shape_mouths` reshapes, and how
+pub struct MouthOptions {
+    // Discharge at the mouth, as a fraction of all circumcenters
+    pub min_discharge: f64,
+    // Sediment load, as discharge fraction times river slope, per unit of
+    // coastal slope, from which a mouth builds a delta instead of an estuary
+    pub delta_threshold: f64,
+    // Height of deposited land above the sea, and depth of drowned valleys below it
+    pub depth: f64,
+    // Delta and estuary length in nodes, per square root of discharge
+    pub size: f64,
+}
+
+impl Default for MouthOptions {
+    fn default() -> MouthOptions {
+        MouthOptions {
+            min_discharge: 0.002,
+            delta_threshold: 0.005,
+            depth: 0.004,
+            size: 0.3,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MouthKind {
+    Delta,
+    Estuary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Mouth {
+    pub kind: MouthKind,
+    // Index of the river, which is the root of its network
+    pub stream: usize,
+    // Circumcenter where the river meets the sea, and a delta fans out from
+    pub apex: usize,
+    // Delta channels from the apex out to sea, downstream. Empty for estuaries.
+    pub distributaries: Vec<Vec<usize>>,
+}
+
+impl MouthKind {
+    pub fn from_index(index: usize) -> Option<MouthKind> {
+        match index {
+            0 => Some(MouthKind::Delta),
+            1 => Some(MouthKind::Estuary),
+            _ => None,
+        }
+    }
+}
+
+// Where a large river meets the sea, either deposits land it branches across,
+// or drowns its lower valley into a widening estuary. Steep rivers carrying a
+// lot onto a gentle coast build deltas; the rest make estuaries.
+//
+// Raises or lowers `heights` and the `cell_heights` around them, so coast lines
+// found afterwards follow the new shores.
+pub fn shape_mouths(
+    heights: &mut [f64],
+    cell_heights: &mut [f64],
+    voronoi: &Voronoi,
+    network: &RiverNetwork,
+    sea_level: f64,
+    options: &MouthOptions,
+) -> Vec<Mouth> {
+    let circumcenters = &voronoi.circumcenters;
+    let adjacent = &voronoi.adjacent;
+    let voronoi_points = &voronoi.voronoi_points;
+    let voronoi_cells = &voronoi.voronoi_cells;
+    let point = |t: usize| [circumcenters[t * 2], circumcenters[t * 2 + 1]];
+    let distance = |a: usize, b: usize| {
+        let (a, b) = (point(a), point(b));
+        libm::hypot(b[0] - a[0], b[1] - a[1])
+    };
+
+    // Slopes and paths are read from the heights before any mouth was shaped
+    let original = heights.to_vec();
+    let min_discharge = options.min_discharge * heights.len() as f64;
+
+    let mut mouths = Vec::new();
+    let mut changed = HashSet::new();
+    for (s, stream) in network.streams.iter().enumerate() {
+        let nodes = &stream.nodes;
+        if stream.parent.is_some() || nodes.len() < 2 || stream.discharge[0] < min_discharge {
+            continue;
+        }
+        if original[nodes[0]] >= sea_level {
+            continue; // Ends in a sink
+        }
+        let i = match nodes.iter().position(|&t| original[t] >= sea_level) {
+            Some(i) => i,
+            None => continue,
+        };
+        let apex = nodes[i];
+
+        let upstream = (i + RIVER_REACH).min(nodes.len() - 1);
+        let length = (i..upstream)
+            .map(|k| distance(nodes[k], nodes[k + 1]))
+            .sum::<f64>();
+        let river_slope = if length > 0. {
+            (original[nodes[upstream]] - original[apex]).max(0.) / length
+        } else {
+            0.
+        };
+        let coast_slope = coast_slope(apex, &original, adjacent, sea_level, &distance);
+
+        let discharge = stream.discharge[0];
+        let sediment = discharge / heights.len() as f64 * river_slope;
+        let size = ((discharge.sqrt() * options.size).ceil() as usize).max(2);
+
+        let mouth = if sediment >= options.delta_threshold * coast_slope {
+            let distributaries =
+                get_distributaries(apex, size, discharge, &original, adjacent, sea_level);
+            deposit(
+                &distributaries,
+                heights,
+                voronoi_points,
+                voronoi_cells,
+                sea_level,
+                options.depth,
+                &mut changed,
+            );
+            Mouth {
+                kind: MouthKind::Delta,
+                stream: s,
+                apex,
+                distributaries,
+            }
+        } else {
+            let valley = &nodes[i..(i + size).min(nodes.len())];
+            drown(
+                valley,
+                heights,
+                voronoi_points,
+                voronoi_cells,
+                sea_level,
+                options.depth,
+                &mut changed,
+            );
+            Mouth {
+                kind: MouthKind::Estuary,
+                stream: s,
+                apex,
+                distributaries: Vec::new(),
+            }
+        };
+        mouths.push(mouth);
+    }
+
+    // Cells are the mean of their corners, as in `get_cell_heights`
+    let mut changed = changed.into_iter().collect::<Vec<_>>();
+    changed.sort_unstable();
+    for t in changed {
+        for &cell in voronoi_cells[t].iter() {
+            let points = &voronoi_points[cell];
+            cell_heights[cell] =
+                points.iter().map(|&n| heights[n]).sum::<f64>() / points.len() as f64;
+        }
+    }
+
+    mouths
+}
+
+// Average drop of the sea floor per unit of distance from the apex
+fn coast_slope(
+    apex: usize,
+    heights: &[f64],
+    adjacent: &Graph,
+    sea_level: f64,
+    distance: &dyn Fn(usize, usize) -> f64,
+) -> f64 {
+    let mut seen = HashSet::new();
+    seen.insert(apex);
+    let mut ring = vec![apex];
+    let (mut sum, mut count) = (0., 0);
+
+    for _ in 0..COAST_RINGS {
+        let mut next = Vec::new();
+        for &t in ring.iter() {
+            for &n in adjacent[t].iter() {
+                if heights[n] >= sea_level || !seen.insert(n) {
+                    continue;
+                }
+                let d = distance(apex, n);
+                if d > 0. {
+                    sum += (sea_level - heights[n]) / d;
+                    count += 1;
+                }
+                next.push(n);
+            }
+        }
+        ring = next;
+    }
+
+    if count > 0 {
+        sum / count as f64
+    } else {
+        0.
+    }
+}
+
+// Channels walking seaward from the apex, each taking the lowest free neighbor
+// that is downhill or under the sea. Every few nodes a channel splits off
+// another through the second lowest, up to a number of channels growing with
+// discharge.
+fn get_distributaries(
+    apex: usize,
+    size: usize,
+    discharge: f64,
+    heights: &[f64],
+    adjacent: &Graph,
+    sea_level: f64,
+) -> Vec<Vec<usize>> {
+    let max_channels = ((discharge.sqrt() / 10.) as usize).clamp(2, 8);
+
+    let mut taken = HashSet::new();
+    taken.insert(apex);
+    let mut channels = vec![vec![apex]];
+    let mut active = vec![0];
+
+    for step in 1..size {
+        let mut next_active = Vec::new();
+        for &c in active.iter() {
+            let last = *channels[c].last().unwrap();
+            let mut lower = adjacent[last]
+                .iter()
+                .cloned()
+                .filter(|&n| heights[n] < heights[last].max(sea_level) && !taken.contains(&n))
+                .collect::<Vec<_>>();
+            lower.sort_unstable_by(|&a, &b| heights[a].total_cmp(&heights[b]).then(a.cmp(&b)));
+
+            let mut lower = lower.into_iter();
+            let first = match lower.next() {
+                Some(n) => n,
+                None => continue, // Reached the deep sea or the edge
+            };
+            taken.insert(first);
+            channels[c].push(first);
+            next_active.push(c);
+
+            if step % SPLIT_EVERY == 0 && channels.len() < max_channels {
+                if let Some(second) = lower.next() {
+                    taken.insert(second);
+                    channels.push(vec![last, second]);
+                    next_active.push(channels.len() - 1);
+                }
+            }
+        }
+        if next_active.is_empty() {
+            break;
+        }
+        active = next_active;
+    }
+
+    channels.retain(|channel| channel.len() > 1);
+    channels
+}
+
+// Builds land along the channels, just above the sea, with the channels
+// themselves a little lower. The last node of each channel is left as its
+// outlet into the sea.
+fn deposit(
+    channels: &[Vec<usize>],
+    heights: &mut [f64],
+    voronoi_points: &Graph,
+    voronoi_cells: &Graph,
+    sea_level: f64,
+    depth: f64,
+    changed: &mut HashSet<usize>,
+) {
+    let water: HashSet<usize> = channels.iter().flatten().cloned().collect();
+    let inner = channels
+        .iter()
+        .flat_map(|channel| channel[..channel.len() - 1].iter().cloned());
+
+    for t in inner {
+        for &cell in voronoi_cells[t].iter() {
+            for &n in voronoi_points[cell].iter() {
+                if !water.contains(&n) && heights[n] < sea_level + depth {
+                    heights[n] = sea_level + depth;
+                    changed.insert(n);
+                }
+            }
+        }
+        if heights[t] < sea_level + depth / 2. {
+            heights[t] = sea_level + depth / 2.;
+            changed.insert(t);
+        }
+    }
+}
+
+// Sinks the valley below the sea, its whole cells near the apex and only the
+// riverbed further up, so the estuary narrows inland.
+fn drown(
+    valley: &[usize],
+    heights: &mut [f64],
+    voronoi_points: &Graph,
+    voronoi_cells: &Graph,
+    sea_level: f64,
+    depth: f64,
+    changed: &mut HashSet<usize>,
+) {
+    let mut lower = |n: usize, height: f64| {
+        if heights[n] > height {
+            heights[n] = height;
+            changed.insert(n);
+        }
+    };
+
+    for (k, &t) in valley.iter().enumerate() {
+        let u = k as f64 / valley.len() as f64;
+        let height = sea_level - depth * (1. - u);
+        if u < 0.5 {
+            for &cell in voronoi_cells[t].iter() {
+                for &n in voronoi_points[cell].iter() {
+                    lower(n, height);
+                }
+            }
+        }
+        lower(t, height);
+    }
+}
@@ -5,7 +5,9 @@ pub struct Noise {
     height: FastNoise,
     theta: FastNoise,
     offset: FastNoise,
-    // noise_resources: FastNoise,
+    plates: FastNoise,
+    resources: FastNoise,
+    seed: u64,
     uniform: RandomNumberGenerator,
 }
 
@@ -27,9 +29,15 @@ impl Noise {
         offset.set_noise_type(NoiseType::Simplex);
         offset.set_frequency(2.0);
 
-        // let mut noise_resources = FastNoise::seeded(seed + 3);
-        // noise_resources.set_noise_type(NoiseType::Simplex);
-        // noise_resources.set_frequency(2.0);
+        // Cellular, with one value per cell standing in for a tectonic plate
+        let mut plates = FastNoise::seeded(seed + 4);
+        plates.set_noise_type(NoiseType::Cellular);
+        plates.set_cellular_return_type(CellularReturnType::CellValue);
+        plates.set_frequency(3.0);
+
+        let mut resources = FastNoise::seeded(seed + 3);
+        resources.set_noise_type(NoiseType::Simplex);
+        resources.set_frequency(2.0);
 
         let uniform = RandomNumberGenerator::seeded(seed);
 
@@ -37,7 +45,9 @@ impl Noise {
             height,
             theta,
             offset,
-            // noise_resources,
+            plates,
+            resources,
+            seed,
             uniform,
         }
     }
@@ -54,10 +64,35 @@ impl Noise {
         self.offset.get_noise(x as f32, y as f32) as f64
     }
 
+    // Patchiness of soils and deposits
+    pub fn resources(&self, x: f64, y: f64) -> f64 {
+        self.resources.get_noise(x as f32, y as f32) as f64
+    }
+
+    // Same for every point on a plate, and different from plate to plate
+    pub fn plate(&self, x: f64, y: f64) -> f64 {
+        // Warped like `fractal_noise`, so boundaries don't run straight
+        let theta = self.theta(x, y);
+        let length = self.offset(x, y) * 0.1;
+        let x = x + libm::cos(theta) * length;
+        let y = y + libm::sin(theta) * length;
+
+        self.plates.get_noise(x as f32, y as f32) as f64
+    }
+
     pub fn rng(&mut self) -> f64 {
         self.uniform.rand::<f64>()
     }
 
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Starts `rng` over from the seed, so sampling again gives the same points
+    pub fn restart_rng(&mut self) {
+        self.uniform = RandomNumberGenerator::seeded(self.seed);
+    }
+
     pub fn fractal_noise(&self, x: f64, y: f64) -> f64 {
         let force = 0.25; // magic
         let wavyness = 5e-1; // magic
@@ -65,9 +100,32 @@ impl Noise {
         let theta = self.theta(x * force, y * force);
         let length = self.offset(x * force, y * force);
 
-        let x = x + theta.cos() * length * wavyness;
-        let y = y + theta.sin() * length * wavyness;
+        let x = x + libm::cos(theta) * length * wavyness;
+        let y = y + libm::sin(theta) * length * wavyness;
 
         self.height(x, y)
     }
 }
+
+// Noise for details added to a finished world, like river meanders, so they can
+// be recomputed from the world alone. Unseeded, unless the caller brings a seed.
+pub struct DetailNoise {
+    noise: FastNoise,
+}
+
+impl DetailNoise {
+    pub fn new(frequency: f64) -> DetailNoise {
+        DetailNoise::seeded(0, frequency)
+    }
+
+    pub fn seeded(seed: u64, frequency: f64) -> DetailNoise {
+        let mut noise = FastNoise::seeded(seed);
+        noise.set_noise_type(NoiseType::Simplex);
+        noise.set_frequency(frequency as f32);
+        DetailNoise { noise }
+    }
+
+    pub fn get(&self, x: f64, y: f64) -> f64 {
+        self.noise.get_noise(x as f32, y as f32) as f64
+    }
+}
@@ -0,0 +1,39 @@
+// Per-index maps that run on rayon's thread pool with the `parallel` feature, and
+// serially otherwise. Results are collected in index order either way, so both
+// paths give identical output.
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+#[cfg(feature = "parallel")]
+pub fn map_range<T, F>(n: usize, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Sync + Send,
+{
+    (0..n).into_par_iter().map(f).collect()
+}
+
+#[cfg(not(feature = "parallel"))]
+pub fn map_range<T, F>(n: usize, f: F) -> Vec<T>
+where
+    F: Fn(usize) -> T,
+{
+    (0..n).map(f).collect()
+}
+
+#[cfg(feature = "parallel")]
+pub fn filter_range<F>(n: usize, f: F) -> Vec<usize>
+where
+    F: Fn(usize) -> bool + Sync + Send,
+{
+    (0..n).into_par_iter().filter(|&i| f(i)).collect()
+}
+
+#[cfg(not(feature = "parallel"))]
+pub fn filter_range<F>(n: usize, f: F) -> Vec<usize>
+where
+    F: Fn(usize) -> bool,
+{
+    (0..n).filter(|&i| f(i)).collect()
+}
@@ -71,8 +71,8 @@ fn sample_poisson_points(
         // Get a sample at some random angle and distance from `point`
         let theta = gen.noise.rng() * PI * 2.0;
         let offset = size + gen.noise.rng() * min_offset;
-        let x = point[0] + theta.cos() * offset;
-        let y = point[1] + theta.sin() * offset;
+        let x = point[0] + libm::cos(theta) * offset;
+        let y = point[1] + libm::sin(theta) * offset;
 
         // If out of lower bounds, keep looking.
         if x < -min_offset || y < -min_offset
@@ -170,7 +170,7 @@ fn add_borders(
     // Top
     for _x in 0..=(1.0 / size) as usize {
         let x = _x as f64 * size;
-        let y = offset * -(x - cx).abs().cos();
+        let y = offset * -libm::cos((x - cx).abs());
         let pos = [x, y];
         let i = (x / 2.0 / size) as usize;
         grid[i].push(pos);
@@ -181,7 +181,7 @@ fn add_borders(
     // Left
     for _y in 0..=(1.0 / size) as usize {
         let y = _y as f64 * size;
-        let x = offset * -(y - cy).abs().cos();
+        let x = offset * -libm::cos((y - cy).abs());
         let pos = [x, y];
         let j = ((y / 2.0 / size) as usize).min(cols - 1);
         grid[j * cols].push(pos);
@@ -192,7 +192,7 @@ fn add_borders(
     // Bottom
     for _x in 0..=(1.0 / size) as usize {
         let x = _x as f64 * size;
-        let y = 1.0 + offset * (x - cx).abs().cos();
+        let y = 1.0 + offset * libm::cos((x - cx).abs());
         let pos = [x, y];
         let i = ((x / 2.0 / size) as usize).min(cols - 1);
         grid[i + (rows - 1) * cols].push(pos);
@@ -203,7 +203,7 @@ fn add_borders(
     // Right
     for _y in 0..=(1.0 / size) as usize {
         let y = _y as f64 * size;
-        let x = 1.0 + offset * (y - cy).abs().cos();
+        let x = 1.0 + offset * libm::cos((y - cy).abs());
         let pos = [x, y];
         let j = ((y / 2.0 / size) as usize).min(cols - 1);
         grid[cols - 1 + j * cols].push(pos);
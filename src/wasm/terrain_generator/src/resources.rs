@@ -0,0 +1,259 @@
+use super::bathymetry::DepthClass;
+use super::climate::{Climate, MONTHS};
+use super::graph::Graph;
+use super::mouths::Mouth;
+use super::noise::Noise;
+use super::river_network::RiverNetwork;
+use super::voronoi::Voronoi;
+
+// Distance from a river at which its water and silt are down to a third
+const RIVER_REACH: f64 = 0.02;
+// Distance from a plate boundary at which ore is down to a third
+const ORE_REACH: f64 = 0.05;
+// Rise over run between neighbouring cells from which soil washes off, and
+// bare rock shows
+pub const STEEP: f64 = 2.;
+// Yearly precipitation, in mm, that waters crops fully
+const WET: f64 = 1000.;
+// Months need to be warmer than this, in °C, to grow anything
+const GROWING: f64 = 5.;
+
+// Where `Resources::new` places deposits
+pub struct ResourceOptions {
+    // Closest two deposits of the same kind can be
+    pub spacing: f64,
+    // Richness a cell needs to hold a deposit, from 0 to 1
+    pub min_richness: f64,
+}
+
+impl Default for ResourceOptions {
+    fn default() -> ResourceOptions {
+        ResourceOptions {
+            spacing: 0.05,
+            min_richness: 0.4,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DepositKind {
+    Ore,
+    Stone,
+    Timber,
+    Fish,
+}
+
+impl DepositKind {
+    pub fn from_index(index: usize) -> Option<DepositKind> {
+        match index {
+            0 => Some(DepositKind::Ore),
+            1 => Some(DepositKind::Stone),
+            2 => Some(DepositKind::Timber),
+            3 => Some(DepositKind::Fish),
+            _ => None,
+        }
+    }
+}
+
+// What `Resources::new` reads off the world
+pub struct Inputs<'a> {
+    pub voronoi: &'a Voronoi,
+    pub cell_heights: &'a [f64],
+    pub network: &'a RiverNetwork,
+    pub mouths: &'a [Mouth],
+    pub depth_classes: &'a [DepthClass],
+    pub climate: &'a Climate,
+    pub forest: &'a [f64],
+    pub sea_level: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Deposit {
+    pub kind: DepositKind,
+    pub cell: usize,
+    // From the least worth settling for, up to 1
+    pub richness: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Resources {
+    // How well each cell grows crops, from 0 to 1. Zero at sea and under ice.
+    pub fertility: Vec<f64>,
+    // Richest first
+    pub deposits: Vec<Deposit>,
+}
+
+impl Resources {
+    // Fertile land is warm for long enough, watered by rain or rivers, and
+    // flat and low enough to keep the silt rivers bring down, most of all on
+    // deltas.
+    //
+    // Ore lies in mountains near where plates meet, stone where slopes are
+    // too steep for soil, timber in thick forests, and fish over shelves, more so in
+    // cold water. Noise makes them patchy, and the richest spots, spread out,
+    // become deposits.
+    pub fn new(inputs: &Inputs, noise: &Noise, options: &ResourceOptions) -> Resources {
+        let Inputs {
+            voronoi,
+            cell_heights,
+            network,
+            mouths,
+            depth_classes,
+            climate,
+            forest,
+            sea_level,
+        } = *inputs;
+        let points = &voronoi.delaunay.points;
+        let neighbors = &voronoi.delaunay.neighbors;
+        let cells = cell_heights.len();
+        let position = |i: usize| [points[i * 2], points[i * 2 + 1]];
+        let land = |i: usize| cell_heights[i] >= sea_level;
+        let elevation = |i: usize| ((cell_heights[i] - sea_level) / (1. - sea_level)).max(0.);
+        let slopes = get_slopes(points, neighbors, cell_heights);
+
+        // Cells along rivers, and the deltas they build
+        let river_cells = |nodes: Vec<usize>| {
+            let mut sources = nodes
+                .into_iter()
+                .flat_map(|t| voronoi.voronoi_cells[t].iter().cloned())
+                .filter(|&c| land(c))
+                .collect::<Vec<_>>();
+            sources.sort_unstable();
+            sources.dedup();
+            sources
+        };
+        let rivers = river_cells(
+            network
+                .streams
+                .iter()
+                .flat_map(|s| s.nodes.clone())
+                .collect(),
+        );
+        let deltas = river_cells(
+            mouths
+                .iter()
+                .flat_map(|m| m.distributaries.concat())
+                .collect(),
+        );
+        let to_river = neighbors.nearest(&rivers, &position);
+        let to_delta = neighbors.nearest(&deltas, &position);
+
+        let fertility = (0..cells)
+            .map(|i| {
+                if !land(i) || climate.glaciers[i] {
+                    return 0.;
+                }
+                let months = &climate.monthly_temperatures[i * MONTHS..(i + 1) * MONTHS];
+                let season = months.iter().filter(|&&t| t > GROWING).count() as f64 / MONTHS as f64;
+
+                let river = libm::exp(-to_river[i].0 / RIVER_REACH);
+                let water = (climate.precipitation[i] / WET).min(1.).max(river);
+                let flat = 1. - (slopes[i] / STEEP).min(1.);
+                let silt = river.max(libm::exp(-to_delta[i].0 / RIVER_REACH));
+                let sediment = flat * (1. - elevation(i)) * (0.5 + 0.5 * silt);
+
+                let [x, y] = position(i);
+                let patch = 1. + 0.2 * noise.resources(x, y);
+                (season * (0.5 * water + 0.5 * sediment) * patch).clamp(0., 1.)
+            })
+            .collect();
+
+        // Cells on another plate than one of their neighbours
+        let plates = (0..cells)
+            .map(|i| {
+                let [x, y] = position(i);
+                noise.plate(x, y)
+            })
+            .collect::<Vec<_>>();
+        let boundaries = (0..cells)
+            .filter(|&i| neighbors[i].iter().any(|&n| plates[n] != plates[i]))
+            .collect::<Vec<_>>();
+        let to_boundary = neighbors.nearest(&boundaries, &position);
+
+        // Each kind is patchy in its own way
+        let patch = |i: usize, kind: DepositKind| {
+            let [x, y] = position(i);
+            let offset = 10. * (kind as usize + 1) as f64;
+            (noise.resources(x + offset, y - offset) + 1.) / 2.
+        };
+        let richness = |i: usize, kind: DepositKind| {
+            let found = match kind {
+                DepositKind::Ore if land(i) => {
+                    libm::exp(-to_boundary[i].0 / ORE_REACH) * (0.4 + 0.6 * elevation(i))
+                }
+                DepositKind::Stone if land(i) => {
+                    (slopes[i] / STEEP).min(1.) * (0.5 + 0.5 * elevation(i))
+                }
+                DepositKind::Timber => forest[i],
+                DepositKind::Fish => {
+                    let cold = ((20. - climate.sea_temperatures[i]) / 20.).clamp(0., 1.);
+                    let waters = match depth_classes[i] {
+                        DepthClass::Shelf => 1.,
+                        DepthClass::Slope => 0.4,
+                        _ => 0.,
+                    };
+                    waters * (0.6 + 0.4 * cold)
+                }
+                _ => 0.,
+            };
+            found * (0.5 + patch(i, kind))
+        };
+
+        let mut deposits: Vec<Deposit> = Vec::new();
+        for &kind in [
+            DepositKind::Ore,
+            DepositKind::Stone,
+            DepositKind::Timber,
+            DepositKind::Fish,
+        ]
+        .iter()
+        {
+            let mut candidates = (0..cells)
+                .map(|i| (i, richness(i, kind).min(1.)))
+                .filter(|&(_, r)| r >= options.min_richness)
+                .collect::<Vec<_>>();
+            candidates.sort_unstable_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+            let first = deposits.len();
+            for (cell, richness) in candidates {
+                let [x, y] = position(cell);
+                let crowded = deposits[first..].iter().any(|d| {
+                    let [dx, dy] = position(d.cell);
+                    libm::hypot(dx - x, dy - y) < options.spacing
+                });
+                if !crowded {
+                    deposits.push(Deposit {
+                        kind,
+                        cell,
+                        richness,
+                    });
+                }
+            }
+        }
+        deposits.sort_by(|a, b| b.richness.total_cmp(&a.richness).then(a.cell.cmp(&b.cell)));
+
+        Resources {
+            fertility,
+            deposits,
+        }
+    }
+}
+
+// Steepest rise over run from each cell to a neighbour
+pub fn get_slopes(points: &[f64], neighbors: &Graph, cell_heights: &[f64]) -> Vec<f64> {
+    (0..cell_heights.len())
+        .map(|i| {
+            neighbors[i]
+                .iter()
+                .map(|&n| {
+                    let run = libm::hypot(
+                        points[n * 2] - points[i * 2],
+                        points[n * 2 + 1] - points[i * 2 + 1],
+                    );
+                    (cell_heights[n] - cell_heights[i]).abs() / run.max(f64::EPSILON)
+                })
+                .fold(0., f64::max)
+        })
+        .collect()
+}
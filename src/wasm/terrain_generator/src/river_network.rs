@@ -0,0 +1,215 @@
+use delaunator::EMPTY;
+use js_sys::{Float64Array, Uint32Array};
+use wasm_bindgen::prelude::*;
+
+// One of the river polylines from `get_rivers`, with the same index, linked to
+// the streams it flows into and out of. Per-node values line up with `nodes`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Stream {
+    // Circumcenters from downstream to upstream. A tributary starts at its
+    // confluence, which is also one of its parent's nodes.
+    pub nodes: Vec<usize>,
+
+    // Vertices drained through each node, counted along the network, so it
+    // adds up at confluences and never drops going downstream.
+    pub discharge: Vec<f64>,
+    pub strahler: Vec<u32>,
+    pub shreve: Vec<u32>,
+
+    // The stream this one flows into, and where among its nodes
+    pub parent: Option<(usize, usize)>,
+
+    // Streams flowing into this one, from downstream to upstream
+    pub tributaries: Vec<usize>,
+
+    // Circumcenter where the whole system drains, to the sea or a sink
+    pub mouth: usize,
+}
+
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RiverNetwork {
+    #[wasm_bindgen(skip)]
+    pub streams: Vec<Stream>,
+}
+
+impl RiverNetwork {
+    // Links the polylines back together. Each river starts at a node some
+    // earlier river already visited, unless it reaches a mouth itself.
+    pub fn new(rivers: &[Vec<(usize, f64)>]) -> RiverNetwork {
+        let size = rivers.iter().flatten().map(|n| n.0 + 1).max().unwrap_or(0);
+        let mut owner = vec![(EMPTY, 0); size];
+
+        let mut streams: Vec<Stream> = Vec::with_capacity(rivers.len());
+        for (s, river) in rivers.iter().enumerate() {
+            let nodes = river.iter().map(|n| n.0).collect::<Vec<_>>();
+
+            let parent = nodes.first().map(|&n| owner[n]).filter(|p| p.0 != EMPTY);
+            let mouth = match parent {
+                Some((p, _)) => streams[p].mouth,
+                None => nodes.first().cloned().unwrap_or(EMPTY),
+            };
+            if let Some((p, _)) = parent {
+                streams[p].tributaries.push(s);
+            }
+
+            let first_owned = if parent.is_some() { 1 } else { 0 };
+            for (i, &node) in nodes.iter().enumerate().skip(first_owned) {
+                owner[node] = (s, i);
+            }
+
+            let n = nodes.len();
+            streams.push(Stream {
+                nodes,
+                discharge: vec![0.; n],
+                strahler: vec![0; n],
+                shreve: vec![0; n],
+                parent,
+                tributaries: Vec::new(),
+                mouth,
+            });
+        }
+
+        // Tributaries come after the stream they join, so walking backwards
+        // sees every upstream node first.
+        for s in (0..streams.len()).rev() {
+            let mut tributaries = streams[s].tributaries.clone();
+            tributaries.sort_by_key(|&t| streams[t].parent.unwrap().1);
+            streams[s].tributaries = tributaries.clone();
+            let first_owned = if streams[s].parent.is_some() { 1 } else { 0 };
+
+            for i in (first_owned..streams[s].nodes.len()).rev() {
+                // (position, stream) of every node flowing into this one
+                let mut upstream = Vec::new();
+                if i + 1 < streams[s].nodes.len() {
+                    upstream.push((i + 1, s));
+                }
+                while let Some(&t) = tributaries.last() {
+                    if streams[t].parent.unwrap().1 != i {
+                        break;
+                    }
+                    tributaries.pop();
+                    if streams[t].nodes.len() > 1 {
+                        upstream.push((1, t));
+                    }
+                }
+
+                let flux = rivers[s][i].1;
+                let mut discharge = 0.;
+                let mut drained = 0.;
+                let mut shreve = 0;
+                let mut strahler = 0;
+                let mut strahler_count = 0;
+                for &(j, u) in upstream.iter() {
+                    let stream = &streams[u];
+                    discharge += stream.discharge[j];
+                    drained += rivers[u][j].1 + 1.;
+                    shreve += stream.shreve[j];
+                    if stream.strahler[j] > strahler {
+                        strahler = stream.strahler[j];
+                        strahler_count = 1;
+                    } else if stream.strahler[j] == strahler {
+                        strahler_count += 1;
+                    }
+                }
+
+                let stream = &mut streams[s];
+                // Whatever `flux` says drains here from off the network
+                stream.discharge[i] = discharge + (flux + 1. - drained).max(0.);
+                stream.shreve[i] = shreve.max(1);
+                stream.strahler[i] = match strahler_count {
+                    0 => 1,
+                    1 => strahler,
+                    _ => strahler + 1,
+                };
+            }
+
+            // A tributary's confluence carries its own values, for drawing the
+            // segment that joins its parent
+            if first_owned == 1 && streams[s].nodes.len() > 1 {
+                let stream = &mut streams[s];
+                stream.discharge[0] = stream.discharge[1];
+                stream.strahler[0] = stream.strahler[1];
+                stream.shreve[0] = stream.shreve[1];
+            }
+        }
+
+        RiverNetwork { streams }
+    }
+
+    // Circumcenters where river systems end, one per system
+    pub fn mouths(&self) -> Vec<usize> {
+        self.streams
+            .iter()
+            .filter(|stream| stream.parent.is_none())
+            .map(|stream| stream.mouth)
+            .collect()
+    }
+}
+
+// Per-node getters are flattened to line up with `World.riverNodes`.
+#[wasm_bindgen]
+impl RiverNetwork {
+    #[wasm_bindgen(getter)]
+    pub fn discharge(&self) -> Float64Array {
+        let values = self
+            .streams
+            .iter()
+            .flat_map(|s| s.discharge.iter().cloned())
+            .collect::<Vec<f64>>();
+        Float64Array::from(&values[..])
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn strahler(&self) -> Uint32Array {
+        let values = self
+            .streams
+            .iter()
+            .flat_map(|s| s.strahler.iter().cloned())
+            .collect::<Vec<u32>>();
+        Uint32Array::from(&values[..])
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn shreve(&self) -> Uint32Array {
+        let values = self
+            .streams
+            .iter()
+            .flat_map(|s| s.shreve.iter().cloned())
+            .collect::<Vec<u32>>();
+        Uint32Array::from(&values[..])
+    }
+
+    // Index of the river each one flows into, or its own index at a mouth
+    #[wasm_bindgen(getter)]
+    pub fn parents(&self) -> Uint32Array {
+        let values = self
+            .streams
+            .iter()
+            .enumerate()
+            .map(|(s, stream)| stream.parent.map_or(s, |p| p.0) as u32)
+            .collect::<Vec<u32>>();
+        Uint32Array::from(&values[..])
+    }
+
+    // Position of each river's confluence among its parent's nodes, zero at a mouth
+    #[wasm_bindgen(getter)]
+    pub fn confluences(&self) -> Uint32Array {
+        let values = self
+            .streams
+            .iter()
+            .map(|stream| stream.parent.map_or(0, |p| p.1) as u32)
+            .collect::<Vec<u32>>();
+        Uint32Array::from(&values[..])
+    }
+
+    #[wasm_bindgen(js_name = "mouths")]
+    pub fn mouths_js(&self) -> Uint32Array {
+        let values = self
+            .mouths()
+            .iter()
+            .map(|&m| m as u32)
+            .collect::<Vec<u32>>();
+        Uint32Array::from(&values[..])
+    }
+}
@@ -1,100 +1,162 @@
 use super::erosion::get_flux;
+use super::graph::Graph;
+use super::noise::DetailNoise;
+use super::river_network::RiverNetwork;
 
 type River = Vec<(usize, f64)>;
 
-pub fn get_river(
-    heights: &Vec<f64>,
-    adjacent: &Vec<Vec<usize>>,
-    flux: &Vec<f64>,
-    sea_level: f64,
-    voronoi_cells: &Vec<Vec<usize>>,
-    cell_heights: &Vec<f64>,
-    mut visited: &mut [bool],
-    i: usize,
-    mut river: Vec<(usize, f64)>,
-) -> (River, Vec<River>) {
-    visited[i] = true; // Whatever happens next, mark this node as visited
-    let height = heights[i];
-
-    if height < sea_level {
-        // If we're undersea, check if at least two adjacent cells are land
-        let cells = &voronoi_cells[i];
-        let num_adjacent = cells
-            .iter()
-            .filter(|cell| cell_heights[**cell] > sea_level)
-            .count();
+// Which rivers `get_rivers` keeps
+pub struct RiverThresholds {
+    // Largest flux along the river
+    pub min_flux: f64,
+    // Nodes, counting a tributary's confluence
+    pub min_length: usize,
+    // Strahler order, counted before pruning. Lower order sources are trimmed off.
+    pub min_order: u32,
+    // Drop rivers that don't flow, through kept rivers, into the sea or a sink
+    pub reach_water: bool,
+}
 
-        // If not, return empty
-        if num_adjacent < 2 {
-            return (river, Vec::new());
-        }
-    }
+// What `get_river` walks, the same for every river
+pub struct Terrain<'a> {
+    pub heights: &'a [f64],
+    pub adjacent: &'a Graph,
+    pub flux: &'a [f64],
+    pub sea_level: f64,
+    pub voronoi_cells: &'a Graph,
+    pub cell_heights: &'a [f64],
+}
 
-    river.push((i, flux[i])); // Include this node to the main river
+// A node whose upstream neighbors are still being walked
+struct Frame {
+    node: usize,
+    neighbors: Vec<usize>,
+    next: usize,
+    main_branch_found: bool,
+    river: usize,
+}
 
-    let mut tributaries: Vec<River> = Vec::new(); // Find rivers that run into this one.
-    let mut main_branch_found = false;
+// Walks upstream from `i`, depth first. The first unvisited neighbor that drains
+// into a node continues its river, and every other one starts a tributary there.
+// Returns the river through `i` followed by all of its tributaries.
+//
+// Uses an explicit stack rather than recursion, since a river and its
+// tributaries can nest deeper than the call stack allows, especially in wasm.
+pub fn get_river(terrain: &Terrain, visited: &mut [bool], i: usize) -> Vec<River> {
+    let Terrain {
+        heights,
+        adjacent,
+        flux,
+        sea_level,
+        voronoi_cells,
+        cell_heights,
+    } = *terrain;
+    let mut rivers: Vec<River> = vec![Vec::new()];
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut next = Some((i, 0)); // Node to walk next, and the river it extends
 
-    // Check all neighbors by reverse flux order
-    let mut neighbors = adjacent[i].clone();
-    neighbors.sort_unstable_by(|&a, &b| flux[a].partial_cmp(&flux[b]).unwrap().reverse());
+    loop {
+        if let Some((node, river)) = next.take() {
+            visited[node] = true; // Whatever happens next, mark this node as visited
 
-    for neighbor in neighbors {
-        if visited[neighbor] {
-            continue;
+            let mut is_river = true;
+            if heights[node] < sea_level {
+                // If we're undersea, check if at least two adjacent cells are land
+                let num_adjacent = voronoi_cells[node]
+                    .iter()
+                    .filter(|cell| cell_heights[**cell] >= sea_level)
+                    .count();
+
+                // If not, the river ends here
+                is_river = num_adjacent >= 2;
+            }
+
+            if is_river {
+                rivers[river].push((node, flux[node])); // Include this node to the river
+
+                // Check all neighbors by reverse flux order
+                let mut neighbors = adjacent[node].to_vec();
+                neighbors.sort_unstable_by(|&a, &b| {
+                    let order = flux[a].total_cmp(&flux[b]).reverse();
+                    order.then(a.cmp(&b))
+                });
+
+                stack.push(Frame {
+                    node,
+                    neighbors,
+                    next: 0,
+                    main_branch_found: false,
+                    river,
+                });
+            }
         }
-        if adjacent[neighbor].iter().any(|n| heights[*n] < height) {
-            continue; // if there exists a lower neighbor for this neighbor, skip
+
+        let frame = match stack.last_mut() {
+            Some(frame) => frame,
+            None => break,
+        };
+        let height = heights[frame.node];
+
+        // `visited` is checked only now, after earlier branches have been walked
+        let mut neighbor = None;
+        while let Some(&n) = frame.neighbors.get(frame.next) {
+            frame.next += 1;
+            if visited[n] {
+                continue;
+            }
+            if adjacent[n].iter().any(|m| heights[*m] < height) {
+                continue; // if there exists a lower neighbor for this neighbor, skip
+            }
+            neighbor = Some(n);
+            break;
         }
 
-        // Otherwise, continue recursion for either main branch or tributaries
-        if !main_branch_found {
-            main_branch_found = true;
-            let (new_river, mut new_tributaries) = get_river(
-                &heights,
-                &adjacent,
-                &flux,
-                sea_level,
-                &voronoi_cells,
-                &cell_heights,
-                &mut visited,
-                neighbor,
-                river,
-            );
-            river = new_river;
-            tributaries.append(&mut new_tributaries);
+        let neighbor = match neighbor {
+            Some(neighbor) => neighbor,
+            None => {
+                stack.pop();
+                continue;
+            }
+        };
+
+        // Continue either the main branch or a new tributary
+        let river = if !frame.main_branch_found {
+            frame.main_branch_found = true;
+            frame.river
         } else {
-            let (new_river, mut new_tributaries) = get_river(
-                &heights,
-                &adjacent,
-                &flux,
-                sea_level,
-                &voronoi_cells,
-                &cell_heights,
-                &mut visited,
-                neighbor,
-                vec![(i, flux[i])],
-            );
-            tributaries.push(new_river);
-            tributaries.append(&mut new_tributaries);
-        }
+            rivers.push(vec![(frame.node, flux[frame.node])]);
+            rivers.len() - 1
+        };
+        next = Some((neighbor, river));
     }
 
-    (river, tributaries)
+    rivers
 }
 
 pub fn get_rivers(
     heights: &Vec<f64>,
-    adjacent: &Vec<Vec<usize>>,
+    adjacent: &Graph,
     sea_level: f64,
-    voronoi_cells: &Vec<Vec<usize>>,
+    voronoi_cells: &Graph,
     cell_heights: &Vec<f64>,
+    thresholds: &RiverThresholds,
 ) -> Vec<River> {
     let flux = get_flux(heights, adjacent);
 
     let mut points_by_height = (0..heights.len()).collect::<Vec<usize>>();
-    points_by_height.sort_unstable_by(|a, b| heights[*a].partial_cmp(&heights[*b]).unwrap());
+    points_by_height.sort_unstable_by(|a, b| {
+        let order = heights[*a].total_cmp(&heights[*b]);
+        order.then(a.cmp(b))
+    });
 
+    let terrain = Terrain {
+        heights,
+        adjacent,
+        flux: &flux,
+        sea_level,
+        voronoi_cells,
+        cell_heights,
+    };
     let mut visited = vec![false; heights.len()];
     let mut rivers: Vec<River> = Vec::new();
 
@@ -103,23 +165,281 @@ pub fn get_rivers(
             continue;
         }
         // Might want to continue here if height < sea_level.
-        let (new_river, mut new_tributaries) = get_river(
-            &heights,
-            &adjacent,
-            &flux,
-            sea_level,
-            &voronoi_cells,
-            &cell_heights,
-            &mut visited,
-            i,
-            Vec::new(),
-        );
-        rivers.push(new_river);
-        rivers.append(&mut new_tributaries);
+        rivers.extend(get_river(&terrain, &mut visited, i));
+    }
+
+    prune(rivers, heights, adjacent, sea_level, thresholds)
+}
+
+// Trims and drops rivers below `thresholds`, keeping the order of the rest
+fn prune(
+    rivers: Vec<River>,
+    heights: &Vec<f64>,
+    adjacent: &Graph,
+    sea_level: f64,
+    thresholds: &RiverThresholds,
+) -> Vec<River> {
+    let network = RiverNetwork::new(&rivers);
+
+    // Land with nothing lower around is a sink, where a lake would form
+    let is_water =
+        |t: usize| heights[t] < sea_level || adjacent[t].iter().all(|&n| heights[n] >= heights[t]);
+
+    // Nodes kept of each river, zero when dropped
+    let mut lengths = vec![0; rivers.len()];
+    for (s, (river, stream)) in rivers.iter().zip(network.streams.iter()).enumerate() {
+        // Order never rises going upstream, so this only cuts off the source end
+        let length = stream
+            .strahler
+            .iter()
+            .take_while(|&&order| order >= thresholds.min_order)
+            .count();
+
+        let is_long = length >= thresholds.min_length.max(2);
+        // A tributary's confluence is its parent's node, with its parent's flux
+        let first_own = if stream.parent.is_some() { 1 } else { 0 };
+        let is_significant = river[first_own.min(length)..length]
+            .iter()
+            .any(|&(_, flux)| flux >= thresholds.min_flux);
+        // A tributary whose confluence was pruned flows on as a river of its
+        // own, so it needs to reach water itself
+        let reaches_water = match stream.parent {
+            _ if !thresholds.reach_water => true,
+            Some((parent, i)) if lengths[parent] > i => true,
+            _ => stream.nodes.first().is_some_and(|&t| is_water(t)),
+        };
+
+        if is_long && is_significant && reaches_water {
+            lengths[s] = length;
+        }
     }
 
     rivers
         .into_iter()
-        .filter(|r| r.len() > 1)
-        .collect::<Vec<River>>()
+        .zip(lengths)
+        .filter(|&(_, length)| length > 0)
+        .map(|(mut river, length)| {
+            river.truncate(length);
+            river
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct RiverCurveOptions {
+    // Curve points per segment between two nodes
+    pub samples: usize,
+    // Largest sideways shift of a node, as a fraction of the distance to its
+    // neighbors. Reached by the largest rivers on flat ground.
+    pub meander: f64,
+    // Slope at which meandering is halved
+    pub flat_slope: f64,
+}
+
+impl Default for RiverCurveOptions {
+    fn default() -> RiverCurveOptions {
+        RiverCurveOptions {
+            samples: 6,
+            meander: 0.35,
+            flat_slope: 2.,
+        }
+    }
+}
+
+// A river as a dense polyline. Segment `i` of the river, from node `i` to node
+// `i + 1`, is `points[i * samples..=(i + 1) * samples]`.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct RiverCurve {
+    pub points: Vec<[f64; 2]>,
+    pub discharge: Vec<f64>,
+    pub samples: usize,
+}
+
+// Smooths the rivers into curves through their nodes, after shifting each node
+// sideways by some noise so big rivers on flat ground meander. Mouths stay put,
+// and a tributary starts wherever its confluence was shifted to, so the network
+// stays joined. Worlds from the same seed meander the same way.
+pub fn get_river_curves(
+    circumcenters: &Vec<f64>,
+    heights: &Vec<f64>,
+    network: &RiverNetwork,
+    seed: u64,
+    options: &RiverCurveOptions,
+) -> Vec<RiverCurve> {
+    let point = |t: usize| [circumcenters[t * 2], circumcenters[t * 2 + 1]];
+    let distance = |a: [f64; 2], b: [f64; 2]| libm::hypot(b[0] - a[0], b[1] - a[1]);
+
+    // Bends every few cells, whatever the resolution
+    let cells = (heights.len() as f64 / 2.).max(1.);
+    let noise = DetailNoise::seeded(seed, cells.sqrt() / 4.);
+
+    let max_discharge = network
+        .streams
+        .iter()
+        .flat_map(|s| s.discharge.iter().cloned())
+        .fold(1., f64::max);
+
+    let mut shifted: Vec<Vec<[f64; 2]>> = Vec::with_capacity(network.streams.len());
+    for stream in network.streams.iter() {
+        let nodes = &stream.nodes;
+        let mut points = nodes.iter().map(|&t| point(t)).collect::<Vec<_>>();
+
+        for i in 1..nodes.len().saturating_sub(1) {
+            let (prev, next) = (point(nodes[i - 1]), point(nodes[i + 1]));
+            let span = distance(prev, next);
+            if span == 0. {
+                continue;
+            }
+
+            let slope = (heights[nodes[i - 1]] - heights[nodes[i + 1]]).abs() / span;
+            let flatness = options.flat_slope / (options.flat_slope + slope);
+            let size = libm::log(1. + stream.discharge[i]) / libm::log(1. + max_discharge);
+            let shift =
+                noise.get(points[i][0], points[i][1]) * options.meander * flatness * size * span
+                    / 2.;
+
+            // Sideways is perpendicular to the line between the neighbors
+            let normal = [(prev[1] - next[1]) / span, (next[0] - prev[0]) / span];
+            points[i] = [
+                points[i][0] + normal[0] * shift,
+                points[i][1] + normal[1] * shift,
+            ];
+        }
+
+        if let Some((parent, i)) = stream.parent {
+            points[0] = shifted[parent][i];
+        }
+        shifted.push(points);
+    }
+
+    shifted
+        .iter()
+        .zip(network.streams.iter())
+        .map(|(points, stream)| catmull_rom(points, &stream.discharge, options.samples))
+        .collect()
+}
+
+// Centripetal Catmull-Rom through every point, which unlike the uniform kind
+// doesn't loop or overshoot where points bunch up.
+fn catmull_rom(points: &[[f64; 2]], discharge: &[f64], samples: usize) -> RiverCurve {
+    let n = points.len();
+    let samples = samples.max(1);
+    if n < 2 {
+        return RiverCurve {
+            points: points.to_vec(),
+            discharge: discharge.to_vec(),
+            samples,
+        };
+    }
+
+    // Ends are extended by mirroring, so the curve starts and ends straight
+    let mirror = |a: [f64; 2], b: [f64; 2]| [2. * a[0] - b[0], 2. * a[1] - b[1]];
+    let at = |i: isize| match i {
+        -1 => mirror(points[0], points[1]),
+        i if i as usize == n => mirror(points[n - 1], points[n - 2]),
+        i => points[i as usize],
+    };
+    // Knot spacing: the square root of the distance
+    let knot =
+        |a: [f64; 2], b: [f64; 2]| libm::sqrt(libm::hypot(b[0] - a[0], b[1] - a[1])).max(1e-9);
+    let lerp = |a: [f64; 2], b: [f64; 2], t0: f64, t1: f64, t: f64| {
+        let (u, v) = ((t1 - t) / (t1 - t0), (t - t0) / (t1 - t0));
+        [a[0] * u + b[0] * v, a[1] * u + b[1] * v]
+    };
+
+    let mut curve = Vec::with_capacity((n - 1) * samples + 1);
+    let mut values = Vec::with_capacity((n - 1) * samples + 1);
+    for i in 0..n - 1 {
+        let (p0, p1, p2, p3) = (
+            at(i as isize - 1),
+            at(i as isize),
+            at(i as isize + 1),
+            at(i as isize + 2),
+        );
+        let t0 = 0.;
+        let t1 = t0 + knot(p0, p1);
+        let t2 = t1 + knot(p1, p2);
+        let t3 = t2 + knot(p2, p3);
+
+        for k in 0..samples {
+            let s = k as f64 / samples as f64;
+            let t = t1 + (t2 - t1) * s;
+            let a1 = lerp(p0, p1, t0, t1, t);
+            let a2 = lerp(p1, p2, t1, t2, t);
+            let a3 = lerp(p2, p3, t2, t3, t);
+            let b1 = lerp(a1, a2, t0, t2, t);
+            let b2 = lerp(a2, a3, t1, t3, t);
+            curve.push(lerp(b1, b2, t1, t2, t));
+            values.push(discharge[i] * (1. - s) + discharge[i + 1] * s);
+        }
+    }
+    curve.push(points[n - 1]);
+    values.push(discharge[n - 1]);
+
+    RiverCurve {
+        points: curve,
+        discharge: values,
+        samples,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thresholds(min_flux: f64) -> RiverThresholds {
+        RiverThresholds {
+            min_flux,
+            min_length: 2,
+            min_order: 1,
+            reach_water: false,
+        }
+    }
+
+    #[test]
+    fn weak_tributaries_are_pruned() {
+        // A trunk 0 - 1 - 2 - 3, and a tributary joining at 1 from 4, which
+        // drains next to nothing
+        let rivers = vec![
+            vec![(0, 12.), (1, 10.), (2, 6.), (3, 4.)],
+            vec![(1, 10.), (4, 0.5)],
+        ];
+        let heights = vec![0., 0.2, 0.4, 0.6, 0.3];
+        let adjacent = Graph::from_lists(&[vec![1], vec![0, 2, 4], vec![1, 3], vec![2], vec![1]]);
+
+        let kept = prune(rivers.clone(), &heights, &adjacent, 0.1, &thresholds(2.));
+        assert_eq!(kept, vec![rivers[0].clone()]);
+
+        // Unless it drains enough on its own
+        let kept = prune(rivers.clone(), &heights, &adjacent, 0.1, &thresholds(0.5));
+        assert_eq!(kept, rivers);
+    }
+
+    #[test]
+    fn orphaned_tributaries_must_reach_water() {
+        // A trunk 0 - 1 too short to keep, with tributaries joining at its
+        // mouth 0 in the sea, and at 1 on land
+        let rivers = vec![
+            vec![(0, 12.), (1, 10.)],
+            vec![(0, 12.), (2, 6.), (3, 4.)],
+            vec![(1, 10.), (4, 6.), (5, 4.)],
+        ];
+        let heights = vec![0., 0.2, 0.3, 0.5, 0.4, 0.6];
+        let adjacent = Graph::from_lists(&[
+            vec![1, 2],
+            vec![0, 4],
+            vec![0, 3],
+            vec![2],
+            vec![1, 5],
+            vec![4],
+        ]);
+        let thresholds = RiverThresholds {
+            min_length: 3,
+            reach_water: true,
+            ..thresholds(1.)
+        };
+
+        let kept = prune(rivers.clone(), &heights, &adjacent, 0.1, &thresholds);
+        assert_eq!(kept, vec![rivers[1].clone()]);
+    }
 }
@@ -0,0 +1,60 @@
+use super::graph::Graph;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct WorldStats {
+    pub cells: usize,
+    pub land_fraction: f64,
+    pub rivers: usize,
+    pub landmasses: usize,
+    // Size, in cells, of the largest connected piece of land
+    pub largest_landmass: usize,
+}
+
+impl WorldStats {
+    pub fn new(
+        cell_heights: &[f64],
+        neighbors: &Graph,
+        rivers: usize,
+        sea_level: f64,
+    ) -> WorldStats {
+        let cells = cell_heights.len();
+        let is_land = |i: usize| cell_heights[i] >= sea_level;
+        let land = (0..cells).filter(|&i| is_land(i)).count();
+
+        // Flood fill each landmass over the Delaunay neighbours
+        let mut visited = vec![false; cells];
+        let mut landmasses = 0;
+        let mut largest_landmass = 0;
+        let mut stack = Vec::new();
+        for start in 0..cells {
+            if visited[start] || !is_land(start) {
+                continue;
+            }
+            landmasses += 1;
+            visited[start] = true;
+            stack.push(start);
+
+            let mut size = 0;
+            while let Some(i) = stack.pop() {
+                size += 1;
+                for &n in neighbors[i].iter() {
+                    if !visited[n] && is_land(n) {
+                        visited[n] = true;
+                        stack.push(n);
+                    }
+                }
+            }
+            largest_landmass = largest_landmass.max(size);
+        }
+
+        WorldStats {
+            cells,
+            land_fraction: land as f64 / cells as f64,
+            rivers,
+            landmasses,
+            largest_landmass,
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use super::climate::Climate;
+use super::coasts::CoastKind;
+use super::rivers::RiverCurveOptions;
+use super::terrain_generator::World;
+use super::voronoi::Voronoi;
+use std::fmt::Write;
+
+// Mirrors `components/svg/Renderer.svelte`, so exports look like the browser preview.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorMode {
+    Height,
+    Biome,
+}
+
+#[derive(Clone, Debug)]
+pub struct SvgOptions {
+    pub color_mode: ColorMode,
+    pub hill_color: [u8; 3],
+    pub cliff_color: [u8; 3],
+    pub land_color: [u8; 3],
+    pub water_color: [u8; 3],
+    pub depth_color: [u8; 3],
+    // Land under glaciers, in height color mode
+    pub ice_color: [u8; 3],
+    // Fills cells in biome color mode, in `Biome` order
+    pub biome_colors: [[u8; 3]; 10],
+    pub coast_color: [u8; 3],
+    // Colors coasts by kind, in `CoastKind` order: beach, cliff, marsh, rocky
+    pub coast_kind_colors: Option<[[u8; 3]; 4]>,
+    pub river_color: [u8; 3],
+    pub river_cap: f64,
+    pub river_width: f64,
+    // Leaves out streams below this Strahler order
+    pub river_min_order: u32,
+    // Draw rivers as meandering curves rather than straight between nodes
+    pub smooth_rivers: bool,
+    // Draw ocean currents as arrows
+    pub current_arrows: bool,
+    pub current_color: [u8; 3],
+    pub size: u32,
+}
+
+impl Default for SvgOptions {
+    fn default() -> SvgOptions {
+        SvgOptions {
+            color_mode: ColorMode::Height,
+            hill_color: [0xe8, 0xff, 0xcd],
+            cliff_color: [0x2f, 0x2b, 0x29],
+            land_color: [0x33, 0x8a, 0x3e],
+            water_color: [0x11, 0xac, 0xc6],
+            depth_color: [0x04, 0x1a, 0x2d],
+            ice_color: [0xee, 0xf4, 0xff],
+            biome_colors: [
+                [0x11, 0xac, 0xc6], // Ocean
+                [0xf4, 0xf8, 0xfb], // Ice
+                [0xb8, 0xc0, 0xa8], // Tundra
+                [0x3f, 0x6e, 0x52], // Taiga
+                [0x4b, 0x8b, 0x3b], // Temperate forest
+                [0x2d, 0x6b, 0x3c], // Temperate rainforest
+                [0xb5, 0xc9, 0x6a], // Grassland
+                [0xe6, 0xd2, 0x96], // Desert
+                [0xc9, 0xc2, 0x5a], // Savanna
+                [0x1f, 0x7a, 0x2e], // Tropical rainforest
+            ],
+            coast_color: [0x13, 0x3b, 0x66],
+            coast_kind_colors: None,
+            river_color: [0x11, 0xac, 0xc6],
+            river_cap: 80.,
+            river_width: 0.05,
+            river_min_order: 1,
+            smooth_rivers: true,
+            current_arrows: false,
+            current_color: [0xff, 0xff, 0xff],
+            size: 1024,
+        }
+    }
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+// `t = 1` gives `a`, `t = 0` gives `b`, same as `interpolateColor` in the renderer.
+fn interpolate(a: [u8; 3], b: [u8; 3], t: f64) -> [u8; 3] {
+    let t = t.clamp(0., 1.);
+    let mix = |i: usize| (t * a[i] as f64 + (1. - t) * b[i] as f64) as u8;
+    [mix(0), mix(1), mix(2)]
+}
+
+fn deviation(values: &[f64]) -> f64 {
+    let n = values.len() as f64;
+    if n < 2. {
+        return 0.;
+    }
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.);
+    variance.sqrt()
+}
+
+fn color_cell(i: usize, world: &World, options: &SvgOptions) -> [u8; 3] {
+    let voronoi = &world.voronoi;
+    let climate = &world.climate;
+    let sea_level = world.sea_level;
+    let h = world.cell_heights[i];
+
+    if options.color_mode == ColorMode::Biome {
+        return options.biome_colors[climate.biomes[i] as usize];
+    }
+
+    if climate.glaciers[i] {
+        return options.ice_color;
+    }
+
+    if h >= sea_level {
+        let t = (h - sea_level) / (1. - sea_level);
+        let color = interpolate(options.hill_color, options.land_color, t);
+
+        // Steep cells get shaded towards the cliff color
+        let sqrt_point_count = (voronoi.delaunay.points.len() as f64).sqrt();
+        let min = 1. / sqrt_point_count;
+        let max = 2. / sqrt_point_count;
+        let corners = voronoi.voronoi_points[i]
+            .iter()
+            .map(|&t| world.heights[t])
+            .collect::<Vec<_>>();
+        let stddev = deviation(&corners).min(max);
+        if stddev < min {
+            return color;
+        }
+        let u = (stddev - min) / (max - min);
+        interpolate(options.cliff_color, color, u * 0.75)
+    } else {
+        interpolate(options.water_color, options.depth_color, h / sea_level)
+    }
+}
+
+pub fn render(world: &World, options: &SvgOptions) -> String {
+    let voronoi = &world.voronoi;
+    let network = &world.river_network;
+    let c = &voronoi.circumcenters;
+    let point = |t: usize| format!("{:.5},{:.5}", c[t * 2], 1. - c[t * 2 + 1]);
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="-0.1 -0.1 1.2 1.2" width="{0}" height="{0}">"#,
+        options.size
+    )
+    .unwrap();
+
+    svg.push_str("<g class=\"cells\" stroke-linejoin=\"round\">\n");
+    for (i, points) in voronoi.voronoi_points.iter().enumerate() {
+        if points.len() < 3 {
+            continue;
+        }
+        let path = points
+            .iter()
+            .map(|&t| point(t))
+            .collect::<Vec<_>>()
+            .join("L");
+        let fill = hex(color_cell(i, world, options));
+        writeln!(
+            svg,
+            r#"<path d="M{}Z" fill="{1}" stroke="{1}" stroke-width="0.001"/>"#,
+            path, fill
+        )
+        .unwrap();
+    }
+    svg.push_str("</g>\n");
+
+    writeln!(
+        svg,
+        r#"<g class="rivers" stroke="{}" stroke-linecap="round">"#,
+        hex(options.river_color)
+    )
+    .unwrap();
+    // One sample per segment, unshifted, is the straight polyline
+    let curves = if options.smooth_rivers {
+        world.river_curves(&RiverCurveOptions::default())
+    } else {
+        world.river_curves(&RiverCurveOptions {
+            samples: 1,
+            meander: 0.,
+            ..RiverCurveOptions::default()
+        })
+    };
+    let scale = options.river_width / (voronoi.delaunay.points.len() as f64).sqrt();
+    for (stream, curve) in network.streams.iter().zip(curves.iter()) {
+        for i in 1..stream.nodes.len() {
+            let discharge = stream.discharge[i];
+            if discharge < options.river_cap || stream.strahler[i] < options.river_min_order {
+                continue;
+            }
+            let segment = &curve.points[(i - 1) * curve.samples..=i * curve.samples];
+            let points = segment
+                .iter()
+                .map(|p| format!("{:.5},{:.5}", p[0], 1. - p[1]))
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(
+                svg,
+                r#"<polyline points="{}" fill="none" stroke-width="{:.5}"/>"#,
+                points,
+                discharge.sqrt() * scale,
+            )
+            .unwrap();
+        }
+    }
+    // Delta channels share out the river's discharge
+    for mouth in world.mouths.iter() {
+        let discharge = network
+            .streams
+            .get(mouth.stream)
+            .map_or(0., |stream| stream.discharge[0]);
+        let width = (discharge / mouth.distributaries.len() as f64).sqrt() * scale;
+        for channel in mouth.distributaries.iter() {
+            let points = channel
+                .iter()
+                .map(|&t| point(t))
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(
+                svg,
+                r#"<polyline points="{}" fill="none" stroke-width="{:.5}"/>"#,
+                points, width,
+            )
+            .unwrap();
+        }
+    }
+    svg.push_str("</g>\n");
+
+    let coast = |kind: Option<CoastKind>| {
+        world
+            .coast_lines
+            .iter()
+            .zip(world.coast_kinds.iter())
+            .filter(|&(_, &k)| kind.is_none_or(|kind| k == kind))
+            .map(|(&(a, b), _)| format!("M{}L{}", point(a), point(b)))
+            .collect::<String>()
+    };
+    match options.coast_kind_colors {
+        None => writeln!(
+            svg,
+            r#"<path class="coast" d="{}" fill="none" stroke="{}" stroke-width="0.003"/>"#,
+            coast(None),
+            hex(options.coast_color)
+        )
+        .unwrap(),
+        Some(colors) => {
+            let kinds = [
+                (CoastKind::Beach, "beach"),
+                (CoastKind::Cliff, "cliff"),
+                (CoastKind::Marsh, "marsh"),
+                (CoastKind::Rocky, "rocky"),
+            ];
+            for &(kind, name) in kinds.iter() {
+                writeln!(
+                    svg,
+                    r#"<path class="coast {}" d="{}" fill="none" stroke="{}" stroke-width="0.003"/>"#,
+                    name,
+                    coast(Some(kind)),
+                    hex(colors[kind as usize])
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    if options.current_arrows {
+        render_currents(&mut svg, voronoi, &world.climate, options);
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// One arrow per square of a coarse grid, from the first sea cell found in it,
+// so arrows don't crowd each other at any point density
+fn render_currents(svg: &mut String, voronoi: &Voronoi, climate: &Climate, options: &SvgOptions) {
+    const GRID: usize = 48;
+    const LENGTH: f64 = 0.8 / GRID as f64;
+
+    writeln!(
+        svg,
+        r#"<defs><marker id="current-head" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="4" markerHeight="4" orient="auto"><path d="M0,0L10,5L0,10Z" fill="{}"/></marker></defs>"#,
+        hex(options.current_color)
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<g class="currents" stroke="{}" stroke-width="0.0015" marker-end="url(#current-head)">"#,
+        hex(options.current_color)
+    )
+    .unwrap();
+
+    let points = &voronoi.delaunay.points;
+    let currents = &climate.currents;
+    let mut taken = vec![false; GRID * GRID];
+    for i in 0..points.len() / 2 {
+        let (x, y) = (points[i * 2], points[i * 2 + 1]);
+        let (u, v) = (currents[i * 2], currents[i * 2 + 1]);
+        let speed = libm::hypot(u, v);
+        if speed < 0.05 || !(0. ..1.).contains(&x) || !(0. ..1.).contains(&y) {
+            continue;
+        }
+        let square = (y * GRID as f64) as usize * GRID + (x * GRID as f64) as usize;
+        if taken[square] {
+            continue;
+        }
+        taken[square] = true;
+
+        let length = LENGTH * speed.min(1.) / speed;
+        writeln!(
+            svg,
+            r#"<line x1="{:.5}" y1="{:.5}" x2="{:.5}" y2="{:.5}"/>"#,
+            x,
+            1. - y,
+            x + u * length,
+            1. - (y + v * length),
+        )
+        .unwrap();
+    }
+    svg.push_str("</g>\n");
+}
@@ -1,12 +1,35 @@
 use wasm_bindgen::prelude::*;
 
+use super::bathymetry::{get_depth_classes, shape_sea_floor, BathymetryOptions, DepthClass};
+use super::binary::{Reader, Writer};
+use super::climate::{get_summer_temperatures, Climate, ClimateOptions, MONTHS};
+pub use super::climate::{Biome, Koppen};
 use super::coasts::*;
+pub use super::coasts::{CoastDetailOptions, CoastRing};
 use super::erosion::*;
+use super::error::{check_heights, TerrainError};
+use super::geojson::FeatureCollection;
+use super::glaciers::{erode_glaciers, GlacierOptions};
+use super::graph::Graph;
+use super::mesh::{self, Mesh, MeshOptions};
+use super::mouths::{shape_mouths, Mouth, MouthKind, MouthOptions};
 use super::noise::Noise;
+use super::parallel::map_range;
 use super::poisson;
+pub use super::resources::{Deposit, DepositKind};
+use super::resources::{Inputs, ResourceOptions, Resources};
+use super::river_network::RiverNetwork;
 use super::rivers::*;
+pub use super::rivers::{RiverCurve, RiverCurveOptions};
+use super::stats::WorldStats;
+use super::svg::{self, SvgOptions};
+use super::typed_arrays::*;
 use super::utils;
-use super::voronoi::Voronoi;
+use super::vegetation::{get_plants, Vegetation};
+pub use super::vegetation::{PlantKind, PlantOptions, Plants};
+use super::voronoi::{Delaunay, Voronoi};
+use delaunator::EMPTY;
+use js_sys::{Float64Array, Uint32Array};
 
 extern crate web_sys;
 
@@ -20,17 +43,46 @@ macro_rules! log {
 }
 
 #[wasm_bindgen(readonly)]
-#[derive(Serialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct World {
-    voronoi: Voronoi,
-    heights: Vec<f64>,
+    pub(crate) voronoi: Voronoi,
+    pub(crate) heights: Vec<f64>,
 
     #[serde(rename = "cellHeights")]
-    cell_heights: Vec<f64>,
-    rivers: Vec<Vec<(usize, f64)>>,
+    pub(crate) cell_heights: Vec<f64>,
+    pub(crate) rivers: Vec<Vec<(usize, f64)>>,
+
+    #[serde(rename = "riverNetwork")]
+    pub(crate) river_network: RiverNetwork,
+
+    // Deltas and estuaries, already shaped into the heights
+    pub(crate) mouths: Vec<Mouth>,
 
     #[serde(rename = "coastLines")]
-    coast_lines: Vec<(usize, usize)>,
+    pub(crate) coast_lines: Vec<(usize, usize)>,
+
+    // Kind of shore along each of `coast_lines`
+    #[serde(rename = "coastKinds")]
+    pub(crate) coast_kinds: Vec<CoastKind>,
+
+    // Shelf, slope, abyssal plain or trench, per cell
+    #[serde(rename = "depthClasses")]
+    pub(crate) depth_classes: Vec<DepthClass>,
+
+    // Currents, sea and air temperatures, precipitation and biomes per cell
+    pub(crate) climate: Climate,
+
+    // Plant and forest cover per cell
+    pub(crate) vegetation: Vegetation,
+
+    // Fertility per cell, and deposits of ore, stone, timber and fish
+    pub(crate) resources: Resources,
+
+    #[serde(rename = "seaLevel")]
+    pub(crate) sea_level: f64,
+
+    // Seed of the generator, for details drawn from the world later, like meanders
+    pub(crate) seed: u64,
 }
 
 #[wasm_bindgen]
@@ -38,6 +90,757 @@ impl World {
     pub fn as_js_value(&self) -> JsValue {
         JsValue::from_serde(&self).unwrap()
     }
+
+    #[wasm_bindgen(getter)]
+    pub fn points(&self) -> Float64Array {
+        f64_view(&self.voronoi.delaunay.points)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn circumcenters(&self) -> Float64Array {
+        f64_view(&self.voronoi.circumcenters)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn heights(&self) -> Float64Array {
+        f64_view(&self.heights)
+    }
+
+    #[wasm_bindgen(getter = cellHeights)]
+    pub fn cell_heights(&self) -> Float64Array {
+        f64_view(&self.cell_heights)
+    }
+
+    #[wasm_bindgen(getter = seaLevel)]
+    pub fn sea_level(&self) -> f64 {
+        self.sea_level
+    }
+
+    #[wasm_bindgen(getter = voronoiTriangles)]
+    pub fn voronoi_triangles(&self) -> Uint32Array {
+        u32_view(&self.voronoi.voronoi_triangles)
+    }
+
+    #[wasm_bindgen(getter = voronoiAdjacencyOffsets)]
+    pub fn voronoi_adjacency_offsets(&self) -> Uint32Array {
+        u32_view(self.voronoi.adjacent.offsets())
+    }
+
+    #[wasm_bindgen(getter = voronoiAdjacency)]
+    pub fn voronoi_adjacency(&self) -> Uint32Array {
+        u32_view(self.voronoi.adjacent.values())
+    }
+
+    #[wasm_bindgen(getter = voronoiPointsOffsets)]
+    pub fn voronoi_points_offsets(&self) -> Uint32Array {
+        u32_view(self.voronoi.voronoi_points.offsets())
+    }
+
+    #[wasm_bindgen(getter = voronoiPoints)]
+    pub fn voronoi_points(&self) -> Uint32Array {
+        u32_view(self.voronoi.voronoi_points.values())
+    }
+
+    #[wasm_bindgen(getter = riverOffsets)]
+    pub fn river_offsets(&self) -> Uint32Array {
+        csr_offsets(&self.rivers)
+    }
+
+    #[wasm_bindgen(getter = riverNodes)]
+    pub fn river_nodes(&self) -> Uint32Array {
+        let nodes = self
+            .rivers
+            .iter()
+            .flatten()
+            .map(|n| n.0 as u32)
+            .collect::<Vec<u32>>();
+        Uint32Array::from(&nodes[..])
+    }
+
+    #[wasm_bindgen(getter = riverFlux)]
+    pub fn river_flux(&self) -> Float64Array {
+        let flux = self
+            .rivers
+            .iter()
+            .flatten()
+            .map(|n| n.1)
+            .collect::<Vec<f64>>();
+        Float64Array::from(&flux[..])
+    }
+
+    // Links, stream order and discharge for `rivers`, with the same indices
+    #[wasm_bindgen(getter = riverNetwork)]
+    pub fn river_network(&self) -> RiverNetwork {
+        self.river_network.clone()
+    }
+
+    // Smoothed, meandering polylines for `rivers`, with the same indices, as
+    // `{ points, discharge, samples }` objects
+    #[wasm_bindgen(js_name = riverCurves)]
+    pub fn river_curves_js(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.river_curves(&RiverCurveOptions::default())).unwrap()
+    }
+
+    // `{ kind, stream, apex, distributaries }` objects, where `stream` indexes
+    // `rivers` and distributaries are lists of circumcenters
+    #[wasm_bindgen(js_name = mouths)]
+    pub fn mouths_js(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.mouths).unwrap()
+    }
+
+    #[wasm_bindgen(getter = coastLines)]
+    pub fn coast_lines(&self) -> Uint32Array {
+        let lines = self
+            .coast_lines
+            .iter()
+            .flat_map(|&(a, b)| vec![a as u32, b as u32])
+            .collect::<Vec<u32>>();
+        Uint32Array::from(&lines[..])
+    }
+
+    // Coasts chained into detailed `{ points, lines, samples, closed }` rings, for
+    // drawing close up. Each segment is split into `2^levels` pieces, and
+    // `levels` can be at most 8.
+    #[wasm_bindgen(js_name = coastRings)]
+    pub fn coast_rings_js(&self, seed: u32, levels: usize) -> Result<JsValue, TerrainError> {
+        let options = CoastDetailOptions {
+            seed: seed as u64,
+            levels,
+            ..CoastDetailOptions::default()
+        };
+        Ok(serde_wasm_bindgen::to_value(&self.coast_rings(&options)?).unwrap())
+    }
+
+    // One per coast line: 0 beach, 1 cliff, 2 marsh, 3 rocky
+    #[wasm_bindgen(getter = coastKinds)]
+    pub fn coast_kinds(&self) -> Uint32Array {
+        let kinds = self
+            .coast_kinds
+            .iter()
+            .map(|&kind| kind as u32)
+            .collect::<Vec<u32>>();
+        Uint32Array::from(&kinds[..])
+    }
+
+    // One per cell: 0 land, 1 shelf, 2 slope, 3 abyssal, 4 trench
+    #[wasm_bindgen(getter = depthClasses)]
+    pub fn depth_classes(&self) -> Uint32Array {
+        let classes = self
+            .depth_classes
+            .iter()
+            .map(|&class| class as u32)
+            .collect::<Vec<u32>>();
+        Uint32Array::from(&classes[..])
+    }
+
+    // Current of each cell, as x and y pairs. Zero on land.
+    #[wasm_bindgen(getter)]
+    pub fn currents(&self) -> Float64Array {
+        f64_view(&self.climate.currents)
+    }
+
+    #[wasm_bindgen(getter = seaTemperatures)]
+    pub fn sea_temperatures(&self) -> Float64Array {
+        f64_view(&self.climate.sea_temperatures)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn temperatures(&self) -> Float64Array {
+        f64_view(&self.climate.temperatures)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn precipitation(&self) -> Float64Array {
+        f64_view(&self.climate.precipitation)
+    }
+
+    // Twelve per cell, January first, in °C
+    #[wasm_bindgen(getter = monthlyTemperatures)]
+    pub fn monthly_temperatures(&self) -> Float64Array {
+        f64_view(&self.climate.monthly_temperatures)
+    }
+
+    // Twelve per cell, in mm
+    #[wasm_bindgen(getter = monthlyPrecipitation)]
+    pub fn monthly_precipitation(&self) -> Float64Array {
+        f64_view(&self.climate.monthly_precipitation)
+    }
+
+    // Twelve per cell, snow on the ground at each month's end in mm of water
+    #[wasm_bindgen(getter)]
+    pub fn snow(&self) -> Float64Array {
+        f64_view(&self.climate.snow)
+    }
+
+    // One per cell, 1 where ice never melts
+    #[wasm_bindgen(getter)]
+    pub fn glaciers(&self) -> Uint32Array {
+        let glaciers = self
+            .climate
+            .glaciers
+            .iter()
+            .map(|&glacier| glacier as u32)
+            .collect::<Vec<u32>>();
+        Uint32Array::from(&glaciers[..])
+    }
+
+    // Height of the snow line over each cell
+    #[wasm_bindgen(getter = snowLine)]
+    pub fn snow_line(&self) -> Float64Array {
+        f64_view(&self.climate.snow_line)
+    }
+
+    // One per cell, in `Koppen` order, 0 Af to 29 EF
+    #[wasm_bindgen(getter)]
+    pub fn koppen(&self) -> Uint32Array {
+        let koppen = self
+            .climate
+            .koppen
+            .iter()
+            .map(|&class| class as u32)
+            .collect::<Vec<u32>>();
+        Uint32Array::from(&koppen[..])
+    }
+
+    // Twelve factors per river, averaging one, that scale its discharge by month
+    #[wasm_bindgen(getter = riverRegimes)]
+    pub fn river_regimes(&self) -> Float64Array {
+        f64_view(&self.climate.regimes)
+    }
+
+    // One per cell, in `Biome` order, 0 ocean to 9 tropical rainforest
+    #[wasm_bindgen(getter)]
+    pub fn biomes(&self) -> Uint32Array {
+        let biomes = self
+            .climate
+            .biomes
+            .iter()
+            .map(|&biome| biome as u32)
+            .collect::<Vec<u32>>();
+        Uint32Array::from(&biomes[..])
+    }
+
+    // One per cell, the ground covered by plants, from 0 to 1
+    #[wasm_bindgen(getter)]
+    pub fn vegetation(&self) -> Float64Array {
+        f64_view(&self.vegetation.cover)
+    }
+
+    // One per cell, the ground under trees, from 0 to its `vegetation`
+    #[wasm_bindgen(getter)]
+    pub fn forest(&self) -> Float64Array {
+        f64_view(&self.vegetation.forest)
+    }
+
+    // Trees and shrubs scattered over the land, as `{ positions, kinds, cells }`
+    // with one entry per plant
+    #[wasm_bindgen(js_name = plants)]
+    pub fn plants_js(&self, seed: u32) -> JsValue {
+        let options = PlantOptions {
+            seed: seed as u64,
+            ..PlantOptions::default()
+        };
+        serde_wasm_bindgen::to_value(&self.plants(&options)).unwrap()
+    }
+
+    // One per cell, from 0 to 1
+    #[wasm_bindgen(getter)]
+    pub fn fertility(&self) -> Float64Array {
+        f64_view(&self.resources.fertility)
+    }
+
+    #[wasm_bindgen(js_name = deposits)]
+    pub fn deposits_js(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.resources.deposits).unwrap()
+    }
+
+    pub fn stats(&self) -> WorldStats {
+        WorldStats::new(
+            &self.cell_heights,
+            &self.voronoi.delaunay.neighbors,
+            self.rivers.len(),
+            self.sea_level,
+        )
+    }
+
+    pub fn to_geojson(&self) -> String {
+        let collection = FeatureCollection::new(self);
+        serde_json::to_string(&collection).unwrap()
+    }
+
+    pub fn to_svg(&self) -> String {
+        self.render_svg(&SvgOptions::default())
+    }
+
+    pub fn to_obj(&self) -> String {
+        mesh::to_obj(&self.meshes(&MeshOptions::default()))
+    }
+
+    pub fn to_glb(&self) -> Vec<u8> {
+        mesh::to_glb(&self.meshes(&MeshOptions::default()))
+    }
+
+    pub fn save(&self, compress: bool) -> Vec<u8> {
+        let mut w = Writer::new();
+        let voronoi = &self.voronoi;
+        let delaunay = &voronoi.delaunay;
+
+        w.f64s(&delaunay.points);
+        w.indices(&delaunay.hull);
+        w.optional_indices(&delaunay.inedges, EMPTY);
+        w.optional_indices(&delaunay.halfedges, EMPTY);
+        w.indices(&delaunay.triangles);
+        w.graph(&delaunay.neighbors);
+
+        w.f64s(&voronoi.circumcenters);
+        w.graph(&voronoi.adjacent);
+        w.indices(&voronoi.voronoi_triangles);
+        w.graph(&voronoi.voronoi_points);
+        w.graph(&voronoi.voronoi_cells);
+
+        w.f64s(&self.heights);
+        w.f64s(&self.cell_heights);
+        w.lists(
+            &self
+                .rivers
+                .iter()
+                .map(|r| r.iter().map(|n| n.0).collect())
+                .collect::<Vec<_>>(),
+        );
+        w.f64s(
+            &self
+                .rivers
+                .iter()
+                .flatten()
+                .map(|n| n.1)
+                .collect::<Vec<_>>(),
+        );
+        w.indices(
+            &self
+                .coast_lines
+                .iter()
+                .flat_map(|&(a, b)| vec![a, b])
+                .collect::<Vec<_>>(),
+        );
+        w.f64(self.sea_level);
+
+        let mouths = &self.mouths;
+        w.indices(&mouths.iter().map(|m| m.kind as usize).collect::<Vec<_>>());
+        w.indices(&mouths.iter().map(|m| m.stream).collect::<Vec<_>>());
+        w.indices(&mouths.iter().map(|m| m.apex).collect::<Vec<_>>());
+        w.indices(
+            &mouths
+                .iter()
+                .map(|m| m.distributaries.len())
+                .collect::<Vec<_>>(),
+        );
+        w.lists(
+            &mouths
+                .iter()
+                .flat_map(|m| m.distributaries.clone())
+                .collect::<Vec<_>>(),
+        );
+
+        let climate = &self.climate.options;
+        w.f64(climate.south);
+        w.f64(climate.north);
+        w.f64(climate.axial_tilt);
+
+        let resources = &self.resources;
+        w.f64s(&resources.fertility);
+        w.indices(
+            &resources
+                .deposits
+                .iter()
+                .map(|d| d.kind as usize)
+                .collect::<Vec<_>>(),
+        );
+        w.indices(
+            &resources
+                .deposits
+                .iter()
+                .map(|d| d.cell)
+                .collect::<Vec<_>>(),
+        );
+        w.f64s(
+            &resources
+                .deposits
+                .iter()
+                .map(|d| d.richness)
+                .collect::<Vec<_>>(),
+        );
+
+        w.varint(self.seed);
+
+        w.finish(compress)
+    }
+
+    // FNV-1a over the uncompressed save, as hex. Equal worlds give equal
+    // fingerprints on every platform.
+    pub fn fingerprint(&self) -> String {
+        let hash = self
+            .save(false)
+            .iter()
+            .fold(0xcbf29ce484222325u64, |hash, &byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            });
+        format!("{:016x}", hash)
+    }
+
+    pub fn load(bytes: &[u8]) -> Result<World, TerrainError> {
+        World::read(bytes).map_err(TerrainError::InvalidWorld)
+    }
+}
+
+impl World {
+    fn read(bytes: &[u8]) -> Result<World, String> {
+        let mut r = Reader::new(bytes)?;
+
+        let delaunay = Delaunay {
+            points: r.f64s()?,
+            hull: r.indices()?,
+            inedges: r.optional_indices(EMPTY)?,
+            halfedges: r.optional_indices(EMPTY)?,
+            triangles: r.indices()?,
+            neighbors: r.graph()?,
+        };
+        let voronoi = Voronoi {
+            circumcenters: r.f64s()?,
+            delaunay,
+            adjacent: r.graph()?,
+            voronoi_triangles: r.indices()?,
+            voronoi_points: r.graph()?,
+            voronoi_cells: r.graph()?,
+        };
+
+        let heights = r.f64s()?;
+        let cell_heights = r.f64s()?;
+        let river_nodes = r.lists()?;
+        let river_flux = r.f64s()?;
+        if river_flux.len() != river_nodes.iter().map(Vec::len).sum::<usize>() {
+            return Err("River flux does not match river nodes".to_string());
+        }
+        let mut river_flux = river_flux.into_iter();
+        let rivers: Vec<Vec<(usize, f64)>> = river_nodes
+            .into_iter()
+            .map(|nodes| nodes.into_iter().zip(&mut river_flux).collect())
+            .collect();
+        let coast_ends = r.indices()?;
+        if coast_ends.len() % 2 != 0 {
+            return Err("Coast line without an end".to_string());
+        }
+        let coast_lines: Vec<(usize, usize)> =
+            coast_ends.chunks_exact(2).map(|c| (c[0], c[1])).collect();
+        let sea_level = r.f64()?;
+        let mouths = if r.version >= 2 {
+            read_mouths(&mut r)?
+        } else {
+            Vec::new()
+        };
+        let climate_options = if r.version >= 3 {
+            ClimateOptions {
+                south: r.f64()?,
+                north: r.f64()?,
+                axial_tilt: r.f64()?,
+            }
+        } else {
+            ClimateOptions::default()
+        };
+        let resources = if r.version >= 4 {
+            read_resources(&mut r)?
+        } else {
+            Resources {
+                fertility: vec![0.; cell_heights.len()],
+                deposits: Vec::new(),
+            }
+        };
+        let seed = if r.version >= 5 { r.varint()? } else { 0 };
+        r.finish()?;
+        check_indices(
+            &voronoi,
+            &heights,
+            &cell_heights,
+            &rivers,
+            &coast_lines,
+            &mouths,
+            &resources,
+        )?;
+
+        // Derived from the rivers and coasts, so they aren't stored
+        let river_network = RiverNetwork::new(&rivers);
+        let coast_kinds = get_coast_kinds(
+            &coast_lines,
+            &cell_heights,
+            &voronoi.delaunay.points,
+            &voronoi.delaunay.neighbors,
+            &voronoi.voronoi_cells,
+            sea_level,
+        );
+        // Worlds only ever use the default depths, so they aren't stored either
+        let depth_classes =
+            get_depth_classes(&cell_heights, sea_level, &BathymetryOptions::default());
+        let climate = Climate::new(
+            &voronoi.delaunay.points,
+            &voronoi.delaunay.neighbors,
+            &voronoi.voronoi_cells,
+            &cell_heights,
+            &river_network,
+            sea_level,
+            &climate_options,
+        );
+        let vegetation = Vegetation::new(
+            &voronoi.delaunay.points,
+            &voronoi.delaunay.neighbors,
+            &cell_heights,
+            &climate,
+            sea_level,
+        );
+
+        Ok(World {
+            voronoi,
+            heights,
+            cell_heights,
+            rivers,
+            river_network,
+            mouths,
+            coast_lines,
+            coast_kinds,
+            depth_classes,
+            climate,
+            vegetation,
+            resources,
+            sea_level,
+            seed,
+        })
+    }
+
+    pub fn coast_rings(
+        &self,
+        options: &CoastDetailOptions,
+    ) -> Result<Vec<CoastRing>, TerrainError> {
+        options.validate()?;
+        Ok(get_coast_rings(
+            &self.coast_lines,
+            &self.cell_heights,
+            &self.voronoi.circumcenters,
+            &self.voronoi.delaunay.points,
+            &self.voronoi.voronoi_cells,
+            self.sea_level,
+            options,
+        ))
+    }
+
+    // Discharge along each river in `month`, 0 for January
+    pub fn monthly_discharge(&self, month: usize) -> Vec<Vec<f64>> {
+        let regimes = &self.climate.regimes;
+        self.river_network
+            .streams
+            .iter()
+            .enumerate()
+            .map(|(s, stream)| {
+                let factor = regimes[s * MONTHS + month % MONTHS];
+                stream.discharge.iter().map(|d| d * factor).collect()
+            })
+            .collect()
+    }
+
+    pub fn plants(&self, options: &PlantOptions) -> Plants {
+        get_plants(
+            &self.voronoi.delaunay.points,
+            &self.voronoi.circumcenters,
+            &self.voronoi.voronoi_points,
+            &self.vegetation,
+            &self.climate,
+            options,
+        )
+    }
+
+    pub fn river_curves(&self, options: &RiverCurveOptions) -> Vec<RiverCurve> {
+        get_river_curves(
+            &self.voronoi.circumcenters,
+            &self.heights,
+            &self.river_network,
+            self.seed,
+            options,
+        )
+    }
+
+    pub fn render_svg(&self, options: &SvgOptions) -> String {
+        svg::render(self, options)
+    }
+
+    pub fn meshes(&self, options: &MeshOptions) -> Vec<Mesh> {
+        mesh::build(
+            &self.voronoi,
+            &self.heights,
+            &self.cell_heights,
+            &self.climate.glaciers,
+            self.sea_level,
+            options,
+        )
+    }
+}
+
+// Every index a saved world holds must point into the mesh before anything is
+// derived from it, or a corrupt file panics instead of failing to load
+fn check_indices(
+    voronoi: &Voronoi,
+    heights: &[f64],
+    cell_heights: &[f64],
+    rivers: &[Vec<(usize, f64)>],
+    coast_lines: &[(usize, usize)],
+    mouths: &[Mouth],
+    resources: &Resources,
+) -> Result<(), String> {
+    let delaunay = &voronoi.delaunay;
+    let cells = delaunay.points.len() / 2;
+    let nodes = delaunay.triangles.len() / 3;
+    let edges = delaunay.halfedges.len();
+
+    let sizes = [
+        ("points", delaunay.points.len(), cells * 2),
+        ("triangles", delaunay.triangles.len(), nodes * 3),
+        ("inedges", delaunay.inedges.len(), cells),
+        ("halfedges", edges, nodes * 3),
+        ("neighbors", delaunay.neighbors.len(), cells),
+        ("circumcenters", voronoi.circumcenters.len(), nodes * 2),
+        ("adjacent", voronoi.adjacent.len(), nodes),
+        ("voronoi points", voronoi.voronoi_points.len(), cells),
+        ("voronoi cells", voronoi.voronoi_cells.len(), nodes),
+        ("heights", heights.len(), nodes),
+        ("cell heights", cell_heights.len(), cells),
+        ("fertility", resources.fertility.len(), cells),
+    ];
+    for &(name, len, expected) in sizes.iter() {
+        if len != expected {
+            return Err(format!("Expected {} {}, found {}", expected, name, len));
+        }
+    }
+    if voronoi.voronoi_triangles.len() % 3 != 0 {
+        return Err("Voronoi triangles are not triples".to_string());
+    }
+
+    fn within<I: IntoIterator<Item = usize>>(
+        name: &str,
+        values: I,
+        size: usize,
+    ) -> Result<(), String> {
+        match values.into_iter().find(|&i| i >= size) {
+            Some(i) => Err(format!("{} index {} is out of range", name, i)),
+            None => Ok(()),
+        }
+    }
+    // Fans of hull cells close with `EMPTY / 3`
+    let fan = voronoi
+        .voronoi_triangles
+        .iter()
+        .enumerate()
+        .filter(|&(k, &t)| k % 3 != 0 && t != EMPTY / 3)
+        .map(|(_, &t)| t);
+
+    within("Hull", delaunay.hull.iter().cloned(), cells)?;
+    within(
+        "Inedge",
+        delaunay.inedges.iter().cloned().filter(|&e| e != EMPTY),
+        edges,
+    )?;
+    within(
+        "Halfedge",
+        delaunay.halfedges.iter().cloned().filter(|&e| e != EMPTY),
+        edges,
+    )?;
+    within("Triangle", delaunay.triangles.iter().cloned(), cells)?;
+    within(
+        "Neighbor",
+        delaunay.neighbors.values().iter().cloned(),
+        cells,
+    )?;
+    within("Adjacent", voronoi.adjacent.values().iter().cloned(), nodes)?;
+    within(
+        "Voronoi triangle",
+        voronoi.voronoi_triangles.iter().step_by(3).cloned(),
+        cells,
+    )?;
+    within("Voronoi triangle", fan, nodes)?;
+    within(
+        "Voronoi point",
+        voronoi.voronoi_points.values().iter().cloned(),
+        nodes,
+    )?;
+    within(
+        "Voronoi cell",
+        voronoi.voronoi_cells.values().iter().cloned(),
+        cells,
+    )?;
+    within("River", rivers.iter().flatten().map(|n| n.0), nodes)?;
+    within(
+        "Coast",
+        coast_lines.iter().flat_map(|&(a, b)| vec![a, b]),
+        nodes,
+    )?;
+    within(
+        "Mouth stream",
+        mouths.iter().map(|m| m.stream),
+        rivers.len(),
+    )?;
+    within("Mouth apex", mouths.iter().map(|m| m.apex), nodes)?;
+    within(
+        "Distributary",
+        mouths.iter().flat_map(|m| m.distributaries.concat()),
+        nodes,
+    )?;
+    within("Deposit", resources.deposits.iter().map(|d| d.cell), cells)?;
+    Ok(())
+}
+
+fn read_mouths(r: &mut Reader) -> Result<Vec<Mouth>, String> {
+    let kinds = r.indices()?;
+    let streams = r.indices()?;
+    let apexes = r.indices()?;
+    let counts = r.indices()?;
+    let mut distributaries = r.lists()?.into_iter();
+    if streams.len() != kinds.len() || apexes.len() != kinds.len() || counts.len() != kinds.len() {
+        return Err("Mouth sections do not match".to_string());
+    }
+
+    let mut mouths = Vec::with_capacity(kinds.len());
+    for i in 0..kinds.len() {
+        let kind = MouthKind::from_index(kinds[i]).ok_or("Unknown mouth kind")?;
+        let channels = (&mut distributaries).take(counts[i]).collect::<Vec<_>>();
+        if channels.len() != counts[i] {
+            return Err("Missing distributaries".to_string());
+        }
+        mouths.push(Mouth {
+            kind,
+            stream: streams[i],
+            apex: apexes[i],
+            distributaries: channels,
+        });
+    }
+    Ok(mouths)
+}
+
+fn read_resources(r: &mut Reader) -> Result<Resources, String> {
+    let fertility = r.f64s()?;
+    let kinds = r.indices()?;
+    let cells = r.indices()?;
+    let richness = r.f64s()?;
+    if cells.len() != kinds.len() || richness.len() != kinds.len() {
+        return Err("Deposit sections do not match".to_string());
+    }
+
+    let mut deposits = Vec::with_capacity(kinds.len());
+    for i in 0..kinds.len() {
+        deposits.push(Deposit {
+            kind: DepositKind::from_index(kinds[i]).ok_or("Unknown deposit kind")?,
+            cell: cells[i],
+            richness: richness[i],
+        });
+    }
+    Ok(Resources {
+        fertility,
+        deposits,
+    })
 }
 
 #[wasm_bindgen]
@@ -80,71 +883,690 @@ impl TerrainGenerator {
             Some(heights) => heights,
         };
 
-        let noise = |(i, height)| height + self.noise_single(points[i * 2], points[i * 2 + 1]);
+        let noise = |i: usize| heights[i] + self.noise_single(points[i * 2], points[i * 2 + 1]);
 
-        heights.iter().enumerate().map(noise).collect()
+        map_range(heights.len(), noise)
     }
 
-    fn get_cell_heights(
-        n: usize,
-        heights: &Vec<f64>,
-        voronoi_points: &Vec<Vec<usize>>,
-    ) -> Vec<f64> {
-        let mut cell_heights = vec![0.; n];
-        for i in 0..n {
+    fn get_cell_heights(n: usize, heights: &Vec<f64>, voronoi_points: &Graph) -> Vec<f64> {
+        map_range(n, |i| {
             let points = &voronoi_points[i];
-            cell_heights[i] = points.iter().map(|&n| heights[n]).sum::<f64>() / points.len() as f64;
-        }
-        cell_heights
+            points.iter().map(|&n| heights[n]).sum::<f64>() / points.len() as f64
+        })
     }
 
-    pub fn world(&mut self, radius: f64, sea_level: f64) -> World {
+    pub fn world(&mut self, radius: f64, sea_level: f64) -> Result<World, TerrainError> {
         log!("`world` called");
-        let points = poisson::disc_sample(radius, sea_level, self);
-        log!(" ✓ points poissoned");
-        let voronoi = Voronoi::new(points);
-        log!(" ✓ voronoi triangulated");
+        WorldBuilder::new(radius, sea_level).build(self)
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Stage {
+    Sample,
+    Triangulate,
+    Noise,
+    Plateau,
+    Erode,
+    CellHeights,
+    Bathymetry,
+    Glaciers,
+    Rivers,
+    Mouths,
+    CoastErosion,
+    Coasts,
+    Climate,
+    Vegetation,
+    Resources,
+    Done,
+}
 
-        let heights = self.noise_array(&voronoi.circumcenters, None);
-        log!(" ✓ heights noised");
-        let mut heights = plateau(&voronoi.circumcenters, heights);
-        log!(" ·  ✓ and plateaued");
+// Parameters for the stages after the mesh is sampled. These can be changed on a
+// `WorldBuilder` without throwing away the mesh and noise.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorldOptions {
+    pub sea_level: f64,
+    pub erosion_passes: usize,
+    pub erosion_rate: f64,
+    // Rivers need this much flux somewhere along them to be kept
+    pub river_threshold: f64,
+    pub river_min_length: usize,
+    pub river_min_order: u32,
+    // Keep only rivers that end in the sea or a sink
+    pub rivers_reach_water: bool,
+    // Shape deltas and estuaries where large rivers meet the sea
+    pub river_mouths: bool,
+    // Discharge a river needs at its mouth, as a fraction of all circumcenters
+    pub mouth_min_discharge: f64,
+    // Sediment load per unit of coastal slope from which a mouth is a delta
+    pub delta_threshold: f64,
+    // Passes of the sea carving bays into soft, exposed coasts
+    pub coast_erosion_passes: usize,
+    // Reshape the sea floor into shelves, slopes and abyssal plains
+    pub bathymetry: bool,
+    // Cut trenches along plate boundaries in the deep ocean
+    pub trenches: bool,
+    // Latitudes of the bottom and top edges of the map, in degrees
+    pub south_latitude: f64,
+    pub north_latitude: f64,
+    // Degrees, for how strong the seasons are
+    pub axial_tilt: f64,
+    // Carve U-shaped valleys and moraines where glaciers flow
+    pub glacial_erosion: bool,
+}
 
-        for _ in 0..10 {
-            heights = erode(heights, &voronoi.adjacent, sea_level);
+#[wasm_bindgen]
+impl WorldOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new(sea_level: f64) -> WorldOptions {
+        let mouths = MouthOptions::default();
+        let climate = ClimateOptions::default();
+        WorldOptions {
+            sea_level,
+            erosion_passes: 10,
+            erosion_rate: 0.015,
+            river_threshold: 0.,
+            river_min_length: 2,
+            river_min_order: 1,
+            rivers_reach_water: false,
+            river_mouths: true,
+            mouth_min_discharge: mouths.min_discharge,
+            delta_threshold: mouths.delta_threshold,
+            coast_erosion_passes: 0,
+            bathymetry: true,
+            trenches: false,
+            south_latitude: climate.south,
+            north_latitude: climate.north,
+            axial_tilt: climate.axial_tilt,
+            glacial_erosion: false,
         }
+    }
+}
 
-        log!(" ·  ✓ and eroded ×10");
-        let cell_heights = TerrainGenerator::get_cell_heights(
-            voronoi.delaunay.points.len() / 2,
-            &heights,
-            &voronoi.voronoi_points,
-        );
+impl WorldOptions {
+    pub fn validate(&self) -> Result<(), TerrainError> {
+        if !(self.sea_level >= 0. && self.sea_level <= 1.) {
+            return Err(TerrainError::InvalidOption("sea_level"));
+        }
+        if !(self.erosion_rate.is_finite() && self.erosion_rate >= 0.) {
+            return Err(TerrainError::InvalidOption("erosion_rate"));
+        }
+        if self.river_threshold.is_nan() {
+            return Err(TerrainError::InvalidOption("river_threshold"));
+        }
+        if self.mouth_min_discharge.is_nan() {
+            return Err(TerrainError::InvalidOption("mouth_min_discharge"));
+        }
+        if self.delta_threshold.is_nan() {
+            return Err(TerrainError::InvalidOption("delta_threshold"));
+        }
+        if !(self.south_latitude >= -90. && self.south_latitude <= 90.) {
+            return Err(TerrainError::InvalidOption("south_latitude"));
+        }
+        if !(self.north_latitude >= -90. && self.north_latitude <= 90.) {
+            return Err(TerrainError::InvalidOption("north_latitude"));
+        }
+        if !(self.axial_tilt >= 0. && self.axial_tilt <= 90.) {
+            return Err(TerrainError::InvalidOption("axial_tilt"));
+        }
+        Ok(())
+    }
 
-        let rivers = get_rivers(
-            &heights,
-            &voronoi.adjacent,
-            sea_level,
-            &voronoi.voronoi_cells,
-            &cell_heights,
-        );
-        log!(" ✓ rivers flowed");
+    fn river_thresholds(&self) -> RiverThresholds {
+        RiverThresholds {
+            min_flux: self.river_threshold,
+            min_length: self.river_min_length,
+            min_order: self.river_min_order,
+            reach_water: self.rivers_reach_water,
+        }
+    }
 
-        let coast_lines = get_coast_lines(
-            &cell_heights,
-            &voronoi.delaunay.neighbors,
-            &voronoi.voronoi_points,
-            &voronoi.voronoi_cells,
-            sea_level,
-        );
-        log!(" ✓ coasts lines carved");
+    fn mouth_options(&self) -> MouthOptions {
+        MouthOptions {
+            min_discharge: self.mouth_min_discharge,
+            delta_threshold: self.delta_threshold,
+            ..MouthOptions::default()
+        }
+    }
 
-        World {
-            voronoi,
-            heights,
-            cell_heights,
-            rivers,
-            coast_lines,
+    fn bathymetry_options(&self) -> BathymetryOptions {
+        BathymetryOptions {
+            trenches: self.trenches,
+            ..BathymetryOptions::default()
+        }
+    }
+
+    fn bathymetry_changed(&self, other: &WorldOptions) -> bool {
+        self.bathymetry != other.bathymetry || self.trenches != other.trenches
+    }
+
+    fn climate_options(&self) -> ClimateOptions {
+        ClimateOptions {
+            south: self.south_latitude,
+            north: self.north_latitude,
+            axial_tilt: self.axial_tilt,
+        }
+    }
+
+    fn glaciers_changed(&self, other: &WorldOptions) -> bool {
+        self.glacial_erosion != other.glacial_erosion
+            || self.glacial_erosion && self.climate_options() != other.climate_options()
+    }
+
+    fn mouths_changed(&self, other: &WorldOptions) -> bool {
+        self.river_mouths != other.river_mouths
+            || self.mouth_min_discharge != other.mouth_min_discharge
+            || self.delta_threshold != other.delta_threshold
+    }
+
+    fn rivers_changed(&self, other: &WorldOptions) -> bool {
+        self.river_threshold != other.river_threshold
+            || self.river_min_length != other.river_min_length
+            || self.river_min_order != other.river_min_order
+            || self.rivers_reach_water != other.rivers_reach_water
+    }
+}
+
+// Runs the same pipeline as `TerrainGenerator::world`, one stage (or one erosion
+// pass) per `step`, so callers can report progress and preview heights as they go.
+//
+// Stage outputs are kept around, so after `setOptions` only the stages that read
+// a changed option are run again by the next `step` or `finish`.
+#[wasm_bindgen]
+pub struct WorldBuilder {
+    radius: f64,
+    options: WorldOptions,
+
+    stage: Stage,
+    steps: usize,
+    eroded: usize,
+    progress_callback: Option<js_sys::Function>,
+
+    points: Vec<f64>,
+    voronoi: Option<Voronoi>,
+    plateau_heights: Vec<f64>,
+    heights: Vec<f64>,
+    cell_heights: Vec<f64>,
+    // Heights before the sea floor was shaped
+    eroded_heights: Vec<f64>,
+    eroded_cell_heights: Vec<f64>,
+    // Heights before glaciers carved them
+    floor_heights: Vec<f64>,
+    floor_cell_heights: Vec<f64>,
+    rivers: Vec<Vec<(usize, f64)>>,
+    river_network: RiverNetwork,
+    // Heights as the rivers found them, to reshape mouths from again
+    river_heights: Vec<f64>,
+    river_cell_heights: Vec<f64>,
+    mouths: Vec<Mouth>,
+    // Heights before the coasts were eroded
+    mouth_heights: Vec<f64>,
+    mouth_cell_heights: Vec<f64>,
+    coast_lines: Vec<(usize, usize)>,
+    coast_kinds: Vec<CoastKind>,
+    climate: Climate,
+    vegetation: Vegetation,
+    resources: Resources,
+}
+
+#[wasm_bindgen]
+impl WorldBuilder {
+    #[wasm_bindgen(constructor)]
+    pub fn new(radius: f64, sea_level: f64) -> WorldBuilder {
+        WorldBuilder::with_options(radius, WorldOptions::new(sea_level))
+    }
+
+    #[wasm_bindgen(js_name = "withOptions")]
+    pub fn with_options(radius: f64, options: WorldOptions) -> WorldBuilder {
+        WorldBuilder {
+            radius,
+            options,
+
+            stage: Stage::Sample,
+            steps: 0,
+            eroded: 0,
+            progress_callback: None,
+
+            points: Vec::new(),
+            voronoi: None,
+            plateau_heights: Vec::new(),
+            heights: Vec::new(),
+            cell_heights: Vec::new(),
+            eroded_heights: Vec::new(),
+            eroded_cell_heights: Vec::new(),
+            floor_heights: Vec::new(),
+            floor_cell_heights: Vec::new(),
+            rivers: Vec::new(),
+            river_network: RiverNetwork::default(),
+            river_heights: Vec::new(),
+            river_cell_heights: Vec::new(),
+            mouths: Vec::new(),
+            mouth_heights: Vec::new(),
+            mouth_cell_heights: Vec::new(),
+            coast_lines: Vec::new(),
+            coast_kinds: Vec::new(),
+            climate: Climate::default(),
+            vegetation: Vegetation::default(),
+            resources: Resources::default(),
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn options(&self) -> WorldOptions {
+        self.options
+    }
+
+    // The sea level decides point density, so changing it samples the mesh,
+    // triangulates and noises it again, and the whole world is rebuilt. Only the
+    // other options skip the stages before the ones that read them.
+    #[wasm_bindgen(js_name = "setOptions")]
+    pub fn set_options(&mut self, options: WorldOptions) {
+        let old = self.options;
+        self.options = options;
+
+        if old.sea_level != options.sea_level {
+            self.rewind(Stage::Sample);
+        } else if old.erosion_passes != options.erosion_passes
+            || old.erosion_rate != options.erosion_rate
+        {
+            self.restart_erosion();
+        } else if old.bathymetry_changed(&options) {
+            self.rewind(Stage::Bathymetry);
+        } else if old.glaciers_changed(&options) {
+            self.rewind(Stage::Glaciers);
+        } else if old.rivers_changed(&options) {
+            self.rewind(Stage::Rivers);
+        } else if old.mouths_changed(&options) {
+            self.rewind(Stage::Mouths);
+        } else if old.coast_erosion_passes != options.coast_erosion_passes {
+            self.rewind(Stage::CoastErosion);
+        } else if old.climate_options() != options.climate_options() {
+            self.rewind(Stage::Climate);
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    // Fraction of steps completed, from 0 to 1
+    #[wasm_bindgen(getter)]
+    pub fn progress(&self) -> f64 {
+        self.steps as f64 / self.steps_before(Stage::Done) as f64
+    }
+
+    // Called with `(stage, progress)` after every step
+    #[wasm_bindgen(js_name = "onProgress")]
+    pub fn on_progress(&mut self, callback: js_sys::Function) {
+        self.progress_callback = Some(callback);
+    }
+
+    // Heights of the Voronoi vertices so far, for previews. Empty until `Noise` has run.
+    #[wasm_bindgen(getter)]
+    pub fn heights(&self) -> Float64Array {
+        f64_view(&self.heights)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn circumcenters(&self) -> Float64Array {
+        match &self.voronoi {
+            Some(voronoi) => f64_view(&voronoi.circumcenters),
+            None => Float64Array::new_with_length(0),
+        }
+    }
+
+    // Runs the next stage. Returns `true` once the world is done. After an error
+    // the failed stage stays current, so fix the options and step again.
+    pub fn step(&mut self, generator: &mut TerrainGenerator) -> Result<bool, TerrainError> {
+        // Sampling never finishes without a positive radius
+        if !(self.radius.is_finite() && self.radius > 0.) {
+            return Err(TerrainError::InvalidOption("radius"));
+        }
+        self.options.validate()?;
+        let sea_level = self.options.sea_level;
+
+        self.stage = match self.stage {
+            Stage::Sample => {
+                // The same points as a fresh generator, however often this runs
+                generator.noise.restart_rng();
+                self.points = poisson::disc_sample(self.radius, sea_level, generator);
+                log!(" ✓ points poissoned");
+                Stage::Triangulate
+            }
+            Stage::Triangulate => {
+                self.voronoi = Some(Voronoi::new(self.points.clone())?);
+                self.points = Vec::new();
+                log!(" ✓ voronoi triangulated");
+                Stage::Noise
+            }
+            Stage::Noise => {
+                let voronoi = self.voronoi.as_ref().unwrap();
+                let heights = generator.noise_array(&voronoi.circumcenters, None);
+                check_heights(&heights)?;
+                self.heights = heights;
+                log!(" ✓ heights noised");
+                Stage::Plateau
+            }
+            Stage::Plateau => {
+                let voronoi = self.voronoi.as_ref().unwrap();
+                self.plateau_heights =
+                    plateau(&voronoi.circumcenters, std::mem::take(&mut self.heights));
+                self.heights = self.plateau_heights.clone();
+                log!(" ·  ✓ and plateaued");
+                self.after_plateau()
+            }
+            Stage::Erode => {
+                let voronoi = self.voronoi.as_ref().unwrap();
+                let rate = self.options.erosion_rate;
+                let heights = erode(self.heights.clone(), &voronoi.adjacent, sea_level, rate);
+                check_heights(&heights)?;
+                self.heights = heights;
+                self.eroded += 1;
+                if self.eroded < self.options.erosion_passes {
+                    Stage::Erode
+                } else {
+                    log!(" ·  ✓ and eroded ×{}", self.eroded);
+                    Stage::CellHeights
+                }
+            }
+            Stage::CellHeights => {
+                let voronoi = self.voronoi.as_ref().unwrap();
+                self.cell_heights = TerrainGenerator::get_cell_heights(
+                    voronoi.delaunay.points.len() / 2,
+                    &self.heights,
+                    &voronoi.voronoi_points,
+                );
+                Stage::Bathymetry
+            }
+            Stage::Bathymetry => {
+                let voronoi = self.voronoi.as_ref().unwrap();
+                self.eroded_heights = self.heights.clone();
+                self.eroded_cell_heights = self.cell_heights.clone();
+                if self.options.bathymetry {
+                    shape_sea_floor(
+                        &mut self.heights,
+                        &mut self.cell_heights,
+                        voronoi,
+                        &generator.noise,
+                        sea_level,
+                        &self.options.bathymetry_options(),
+                    );
+                    log!(" ✓ sea floor shaped");
+                }
+                Stage::Glaciers
+            }
+            Stage::Glaciers => {
+                let voronoi = self.voronoi.as_ref().unwrap();
+                self.floor_heights = self.heights.clone();
+                self.floor_cell_heights = self.cell_heights.clone();
+                if self.options.glacial_erosion {
+                    let summer_temperatures = get_summer_temperatures(
+                        &voronoi.delaunay.points,
+                        &voronoi.delaunay.neighbors,
+                        &self.cell_heights,
+                        sea_level,
+                        &self.options.climate_options(),
+                    );
+                    erode_glaciers(
+                        &mut self.heights,
+                        &mut self.cell_heights,
+                        voronoi,
+                        &summer_temperatures,
+                        sea_level,
+                        &GlacierOptions::default(),
+                    );
+                    log!(" ✓ glaciers carved");
+                }
+                Stage::Rivers
+            }
+            Stage::Rivers => {
+                let voronoi = self.voronoi.as_ref().unwrap();
+                self.rivers = get_rivers(
+                    &self.heights,
+                    &voronoi.adjacent,
+                    sea_level,
+                    &voronoi.voronoi_cells,
+                    &self.cell_heights,
+                    &self.options.river_thresholds(),
+                );
+                self.river_network = RiverNetwork::new(&self.rivers);
+                log!(" ✓ rivers flowed");
+                Stage::Mouths
+            }
+            Stage::Mouths => {
+                let voronoi = self.voronoi.as_ref().unwrap();
+                self.river_heights = self.heights.clone();
+                self.river_cell_heights = self.cell_heights.clone();
+                self.mouths = if self.options.river_mouths {
+                    shape_mouths(
+                        &mut self.heights,
+                        &mut self.cell_heights,
+                        voronoi,
+                        &self.river_network,
+                        sea_level,
+                        &self.options.mouth_options(),
+                    )
+                } else {
+                    Vec::new()
+                };
+                log!(" ✓ river mouths shaped");
+                Stage::CoastErosion
+            }
+            Stage::CoastErosion => {
+                let voronoi = self.voronoi.as_ref().unwrap();
+                self.mouth_heights = self.heights.clone();
+                self.mouth_cell_heights = self.cell_heights.clone();
+                erode_coasts(
+                    &mut self.heights,
+                    &mut self.cell_heights,
+                    voronoi,
+                    sea_level,
+                    self.options.coast_erosion_passes,
+                    generator.noise.seed(),
+                );
+                Stage::Coasts
+            }
+            Stage::Coasts => {
+                let voronoi = self.voronoi.as_ref().unwrap();
+                self.coast_lines = get_coast_lines(
+                    &self.cell_heights,
+                    &voronoi.delaunay.neighbors,
+                    &voronoi.voronoi_points,
+                    &voronoi.voronoi_cells,
+                    sea_level,
+                );
+                self.coast_kinds = get_coast_kinds(
+                    &self.coast_lines,
+                    &self.cell_heights,
+                    &voronoi.delaunay.points,
+                    &voronoi.delaunay.neighbors,
+                    &voronoi.voronoi_cells,
+                    sea_level,
+                );
+                log!(" ✓ coasts lines carved");
+                Stage::Climate
+            }
+            Stage::Climate => {
+                let voronoi = self.voronoi.as_ref().unwrap();
+                self.climate = Climate::new(
+                    &voronoi.delaunay.points,
+                    &voronoi.delaunay.neighbors,
+                    &voronoi.voronoi_cells,
+                    &self.cell_heights,
+                    &self.river_network,
+                    sea_level,
+                    &self.options.climate_options(),
+                );
+                log!(" ✓ currents and climate simulated");
+                Stage::Vegetation
+            }
+            Stage::Vegetation => {
+                let voronoi = self.voronoi.as_ref().unwrap();
+                self.vegetation = Vegetation::new(
+                    &voronoi.delaunay.points,
+                    &voronoi.delaunay.neighbors,
+                    &self.cell_heights,
+                    &self.climate,
+                    sea_level,
+                );
+                log!(" ✓ vegetation grown");
+                Stage::Resources
+            }
+            Stage::Resources => {
+                let voronoi = self.voronoi.as_ref().unwrap();
+                let inputs = Inputs {
+                    voronoi,
+                    cell_heights: &self.cell_heights,
+                    network: &self.river_network,
+                    mouths: &self.mouths,
+                    depth_classes: &self.depth_classes(),
+                    climate: &self.climate,
+                    forest: &self.vegetation.forest,
+                    sea_level,
+                };
+                self.resources =
+                    Resources::new(&inputs, &generator.noise, &ResourceOptions::default());
+                log!(" ✓ resources placed");
+                Stage::Done
+            }
+            Stage::Done => return Ok(true),
+        };
+        self.steps += 1;
+
+        if let Some(callback) = &self.progress_callback {
+            let stage = JsValue::from(self.stage as u32);
+            let progress = JsValue::from(self.progress());
+            callback.call2(&JsValue::NULL, &stage, &progress).ok();
+        }
+
+        Ok(self.stage == Stage::Done)
+    }
+
+    // Runs any remaining stages and returns a copy of the world, keeping the
+    // stage outputs for the next `setOptions`.
+    pub fn finish(&mut self, generator: &mut TerrainGenerator) -> Result<World, TerrainError> {
+        while !self.step(generator)? {}
+        let depth_classes = self.depth_classes();
+
+        Ok(World {
+            voronoi: self.voronoi.clone().unwrap(),
+            heights: self.heights.clone(),
+            cell_heights: self.cell_heights.clone(),
+            rivers: self.rivers.clone(),
+            river_network: self.river_network.clone(),
+            mouths: self.mouths.clone(),
+            coast_lines: self.coast_lines.clone(),
+            coast_kinds: self.coast_kinds.clone(),
+            depth_classes,
+            climate: self.climate.clone(),
+            vegetation: self.vegetation.clone(),
+            resources: self.resources.clone(),
+            sea_level: self.options.sea_level,
+            seed: generator.noise.seed(),
+        })
+    }
+
+    // Runs any remaining stages and hands over the finished world.
+    pub fn build(mut self, generator: &mut TerrainGenerator) -> Result<World, TerrainError> {
+        while !self.step(generator)? {}
+        let depth_classes = self.depth_classes();
+
+        Ok(World {
+            voronoi: self.voronoi.unwrap(),
+            heights: self.heights,
+            cell_heights: self.cell_heights,
+            rivers: self.rivers,
+            river_network: self.river_network,
+            mouths: self.mouths,
+            coast_lines: self.coast_lines,
+            coast_kinds: self.coast_kinds,
+            depth_classes,
+            climate: self.climate,
+            vegetation: self.vegetation,
+            resources: self.resources,
+            sea_level: self.options.sea_level,
+            seed: generator.noise.seed(),
+        })
+    }
+}
+
+impl WorldBuilder {
+    fn depth_classes(&self) -> Vec<DepthClass> {
+        get_depth_classes(
+            &self.cell_heights,
+            self.options.sea_level,
+            &self.options.bathymetry_options(),
+        )
+    }
+
+    fn after_plateau(&self) -> Stage {
+        if self.options.erosion_passes > 0 {
+            Stage::Erode
+        } else {
+            Stage::CellHeights
+        }
+    }
+
+    // Every stage is one step, except erosion which is one per pass
+    fn steps_before(&self, stage: Stage) -> usize {
+        let passes = self.options.erosion_passes;
+        match stage {
+            Stage::Sample => 0,
+            Stage::Triangulate => 1,
+            Stage::Noise => 2,
+            Stage::Plateau => 3,
+            Stage::Erode => 4,
+            Stage::CellHeights => 4 + passes,
+            Stage::Bathymetry => 5 + passes,
+            Stage::Glaciers => 6 + passes,
+            Stage::Rivers => 7 + passes,
+            Stage::Mouths => 8 + passes,
+            Stage::CoastErosion => 9 + passes,
+            Stage::Coasts => 10 + passes,
+            Stage::Climate => 11 + passes,
+            Stage::Vegetation => 12 + passes,
+            Stage::Resources => 13 + passes,
+            Stage::Done => 14 + passes,
+        }
+    }
+
+    // Erodes the plateau again from the first pass, if erosion has started. The
+    // stage after the plateau depends on the passes, so this works even when
+    // they're changed partway through erosion.
+    fn restart_erosion(&mut self) {
+        if self.stage >= Stage::Erode {
+            self.heights = self.plateau_heights.clone();
+            self.eroded = 0;
+            self.stage = self.after_plateau();
+        }
+        self.steps = self.steps_before(self.stage);
+    }
+
+    // Moves back to `stage` if it has already run, so it runs again.
+    fn rewind(&mut self, stage: Stage) {
+        if stage > self.stage {
+            return;
+        }
+        if stage <= Stage::CellHeights && self.stage >= Stage::Erode {
+            self.heights = self.plateau_heights.clone();
+            self.eroded = 0;
+        } else if stage <= Stage::Bathymetry && self.stage > Stage::Bathymetry {
+            self.heights = self.eroded_heights.clone();
+            self.cell_heights = self.eroded_cell_heights.clone();
+        } else if stage <= Stage::Glaciers && self.stage > Stage::Glaciers {
+            self.heights = self.floor_heights.clone();
+            self.cell_heights = self.floor_cell_heights.clone();
+        } else if stage <= Stage::Mouths && self.stage > Stage::Mouths {
+            self.heights = self.river_heights.clone();
+            self.cell_heights = self.river_cell_heights.clone();
+        } else if stage <= Stage::CoastErosion && self.stage > Stage::CoastErosion {
+            self.heights = self.mouth_heights.clone();
+            self.cell_heights = self.mouth_cell_heights.clone();
         }
+        self.stage = stage;
+        self.steps = self.steps_before(stage);
     }
 }
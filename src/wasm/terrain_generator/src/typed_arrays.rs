@@ -0,0 +1,33 @@
+use js_sys::{Float64Array, Uint32Array};
+
+// Views borrow straight from wasm memory, so they are only valid until the next
+// allocation on the Rust side. Callers should upload or `.slice()` them right away.
+
+pub fn f64_view(values: &[f64]) -> Float64Array {
+    unsafe { Float64Array::view(values) }
+}
+
+// `usize` is 32 bits on wasm, so index buffers can be viewed without copying.
+#[cfg(target_arch = "wasm32")]
+pub fn u32_view(values: &[usize]) -> Uint32Array {
+    let values = unsafe { std::slice::from_raw_parts(values.as_ptr() as *const u32, values.len()) };
+    unsafe { Uint32Array::view(values) }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn u32_view(values: &[usize]) -> Uint32Array {
+    let values = values.iter().map(|&v| v as u32).collect::<Vec<u32>>();
+    Uint32Array::from(&values[..])
+}
+
+// Compressed sparse row: list `i` is `values[offsets[i]..offsets[i + 1]]`.
+pub fn csr_offsets<T>(lists: &[Vec<T>]) -> Uint32Array {
+    let mut offsets = Vec::with_capacity(lists.len() + 1);
+    let mut offset = 0;
+    offsets.push(offset);
+    for list in lists.iter() {
+        offset += list.len() as u32;
+        offsets.push(offset);
+    }
+    Uint32Array::from(&offsets[..])
+}
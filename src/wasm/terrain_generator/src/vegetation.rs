@@ -0,0 +1,258 @@
+use super::bathymetry::smoothstep;
+use super::climate::{Biome, Climate, MONTHS};
+use super::graph::Graph;
+use super::parallel::map_range;
+use super::resources::{get_slopes, STEEP};
+use bracket_random::prelude::*;
+
+// Months need to be warmer than this, in °C, for plants to grow
+const GROWING: f64 = 5.;
+// Growing months from which plants cover all the ground they have water for
+const FULL_SEASON: f64 = 6.;
+// Aridity index, yearly precipitation in mm over mean temperature plus 10 °C,
+// below which nothing grows, and above which water no longer holds plants back
+const ARID: f64 = 5.;
+const HUMID: f64 = 35.;
+// Aridity index from which trees start to grow, and from which they take over
+const SAVANNA: f64 = 15.;
+const WOODLAND: f64 = 35.;
+// Warmest month, in °C, trees need. Trees take over 4 °C above it.
+const TREE_LINE: f64 = 10.;
+// Mean temperature, in °C, below which trees are conifers
+const CONIFERS: f64 = 6.;
+// Candidates tried around each plant before giving up on it
+const TRIES: usize = 20;
+
+// How `get_plants` scatters plants
+pub struct PlantOptions {
+    pub seed: u64,
+    // Closest two plants are, in the densest forest
+    pub spacing: f64,
+    // Cells covered less than this are left bare
+    pub min_cover: f64,
+}
+
+impl Default for PlantOptions {
+    fn default() -> PlantOptions {
+        PlantOptions {
+            seed: 0,
+            spacing: 0.002,
+            min_cover: 0.05,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlantKind {
+    Broadleaf,
+    Conifer,
+    Shrub,
+}
+
+// Plants in every cell, one per entry. `cells` are the cells they stand in.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Plants {
+    pub positions: Vec<[f64; 2]>,
+    pub kinds: Vec<PlantKind>,
+    pub cells: Vec<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Vegetation {
+    // Ground covered by plants of any kind, per cell, from 0 to 1
+    pub cover: Vec<f64>,
+    // Ground under trees, per cell, from 0 to `cover`
+    pub forest: Vec<f64>,
+}
+
+impl Vegetation {
+    // Plants need a long enough warm season, water, and soil, which washes off
+    // steep slopes and is bare above the snow line. Trees need more of all
+    // three: a warm enough summer and wetter ground, or the rest is grass and
+    // shrubs.
+    pub fn new(
+        points: &[f64],
+        neighbors: &Graph,
+        cell_heights: &[f64],
+        climate: &Climate,
+        sea_level: f64,
+    ) -> Vegetation {
+        let slopes = get_slopes(points, neighbors, cell_heights);
+        let (cover, forest) = (0..cell_heights.len())
+            .map(|i| {
+                if cell_heights[i] < sea_level
+                    || climate.glaciers[i]
+                    || cell_heights[i] >= climate.snow_line[i]
+                {
+                    return (0., 0.);
+                }
+                let months = &climate.monthly_temperatures[i * MONTHS..(i + 1) * MONTHS];
+                let growing = months.iter().filter(|&&t| t > GROWING).count() as f64;
+                let warmest = months.iter().cloned().fold(f64::MIN, f64::max);
+                let aridity = climate.precipitation[i] / (climate.temperatures[i] + 10.).max(1.);
+
+                let season = (growing / FULL_SEASON).min(1.);
+                let water = smoothstep((aridity - ARID) / (HUMID - ARID));
+                let soil = 1. - 0.7 * (slopes[i] / STEEP).min(1.);
+                let cover = season * water * soil;
+
+                let trees = smoothstep((warmest - TREE_LINE) / 4.)
+                    * smoothstep((aridity - SAVANNA) / (WOODLAND - SAVANNA));
+                (cover, cover * trees)
+            })
+            .unzip();
+
+        Vegetation { cover, forest }
+    }
+}
+
+// Scatters plants over each cell, no closer than a spacing that grows where
+// cover is thin and in open biomes. Each is a tree with the chance that
+// ground there is forest, or a shrub. Cells are sampled on their own, each
+// from its own seed, so plants only depend on the cell they're in.
+pub fn get_plants(
+    points: &[f64],
+    circumcenters: &[f64],
+    voronoi_points: &Graph,
+    vegetation: &Vegetation,
+    climate: &Climate,
+    options: &PlantOptions,
+) -> Plants {
+    let vertex = |t: usize| [circumcenters[t * 2], circumcenters[t * 2 + 1]];
+
+    let cells = map_range(voronoi_points.len(), |c| {
+        let cover = vegetation.cover[c];
+        let corners = voronoi_points[c]
+            .iter()
+            .map(|&t| vertex(t))
+            .collect::<Vec<_>>();
+        if cover < options.min_cover || corners.len() < 3 {
+            return Vec::new();
+        }
+        let openness = match climate.biomes[c] {
+            Biome::TropicalRainforest | Biome::TemperateRainforest => 0.8,
+            Biome::TemperateForest => 1.,
+            Biome::Taiga => 1.1,
+            Biome::Grassland => 1.4,
+            Biome::Savanna => 1.6,
+            Biome::Tundra => 1.8,
+            _ => 2.5,
+        };
+        let spacing = options.spacing * openness / cover.sqrt();
+        let tree = if climate.temperatures[c] < CONIFERS {
+            PlantKind::Conifer
+        } else {
+            PlantKind::Broadleaf
+        };
+        let chance = vegetation.forest[c] / cover;
+
+        let mut rng =
+            RandomNumberGenerator::seeded(options.seed.wrapping_mul(0x9e3779b97f4a7c15) ^ c as u64);
+        let site = [points[c * 2], points[c * 2 + 1]];
+        sample_cell(&corners, site, spacing, &mut rng)
+            .into_iter()
+            .map(|position| {
+                let kind = if rng.rand::<f64>() < chance {
+                    tree
+                } else {
+                    PlantKind::Shrub
+                };
+                (position, kind)
+            })
+            .collect()
+    });
+
+    let mut plants = Plants::default();
+    for (c, cell) in cells.into_iter().enumerate() {
+        for (position, kind) in cell {
+            plants.positions.push(position);
+            plants.kinds.push(kind);
+            plants.cells.push(c);
+        }
+    }
+    plants
+}
+
+// Poisson disc sampling inside a convex cell, growing out from its site
+fn sample_cell(
+    corners: &[[f64; 2]],
+    site: [f64; 2],
+    spacing: f64,
+    rng: &mut RandomNumberGenerator,
+) -> Vec<[f64; 2]> {
+    let inside = |p: [f64; 2]| {
+        // On the same side of every edge, without allocating
+        let sides = corners.iter().zip(corners.iter().cycle().skip(1));
+        let (mut left, mut right) = (false, false);
+        for (a, b) in sides {
+            let turn = (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0]);
+            left |= turn > 0.;
+            right |= turn < 0.;
+        }
+        !(left && right)
+    };
+    if !inside(site) {
+        return Vec::new();
+    }
+
+    // Background grid, as in `poisson::disc_sample`, with cells small enough to
+    // hold one sample each, so candidates are only checked against samples
+    // nearby
+    let size = spacing / std::f64::consts::SQRT_2;
+    let (mut min, mut max) = ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]);
+    for corner in corners.iter() {
+        for k in 0..2 {
+            min[k] = min[k].min(corner[k]);
+            max[k] = max[k].max(corner[k]);
+        }
+    }
+    let cols = ((max[0] - min[0]) / size) as usize + 1;
+    let rows = ((max[1] - min[1]) / size) as usize + 1;
+    let grid_cell = |p: [f64; 2]| {
+        let col = (((p[0] - min[0]) / size) as usize).min(cols - 1);
+        let row = (((p[1] - min[1]) / size) as usize).min(rows - 1);
+        (col, row)
+    };
+    let mut grid = vec![None; cols * rows];
+    let (col, row) = grid_cell(site);
+    grid[col + row * cols] = Some(site);
+
+    let mut samples = vec![site];
+    let mut active = vec![site];
+    while !active.is_empty() {
+        let i = (rng.rand::<f64>() * active.len() as f64) as usize;
+        let point = active[i];
+        let mut found = false;
+        for _ in 0..TRIES {
+            let theta = rng.rand::<f64>() * std::f64::consts::PI * 2.;
+            let offset = spacing * (1. + rng.rand::<f64>());
+            let sample = [
+                point[0] + libm::cos(theta) * offset,
+                point[1] + libm::sin(theta) * offset,
+            ];
+            if !inside(sample) {
+                continue;
+            }
+            // Anything closer than `spacing` is at most two grid cells away
+            let (col, row) = grid_cell(sample);
+            let crowded = (row.saturating_sub(2)..(row + 3).min(rows)).any(|r| {
+                (col.saturating_sub(2)..(col + 3).min(cols)).any(|c| match grid[c + r * cols] {
+                    Some(s) => libm::hypot(s[0] - sample[0], s[1] - sample[1]) < spacing,
+                    None => false,
+                })
+            });
+            if !crowded {
+                grid[col + row * cols] = Some(sample);
+                samples.push(sample);
+                active.push(sample);
+                found = true;
+                break;
+            }
+        }
+        if !found {
+            active.swap_remove(i);
+        }
+    }
+    samples
+}
@@ -1,3 +1,5 @@
+use super::error::TerrainError;
+use super::graph::Graph;
 use delaunator::{triangulate, Point, Triangulation, EMPTY};
 
 #[path = "utils.rs"]
@@ -15,46 +17,48 @@ macro_rules! log {
 // Implement _init from here:
 // https://github.com/d3/d3-delaunay/blob/master/src/voronoi.js
 
-#[derive(Serialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Voronoi {
     pub circumcenters: Vec<f64>,
     pub delaunay: Delaunay,
-    pub adjacent: Vec<Vec<usize>>,
+    pub adjacent: Graph,
     pub voronoi_triangles: Vec<usize>,
-    pub voronoi_points: Vec<Vec<usize>>,
-    pub voronoi_cells: Vec<Vec<usize>>,
+    pub voronoi_points: Graph,
+    pub voronoi_cells: Graph,
 }
 
 // #[wasm_bindgen]
-#[derive(Serialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Delaunay {
     pub points: Vec<f64>,
     pub hull: Vec<usize>,
     pub inedges: Vec<usize>,
     pub halfedges: Vec<usize>,
     pub triangles: Vec<usize>,
-    pub neighbors: Vec<Vec<usize>>,
+    pub neighbors: Graph,
 }
 
 // From `../../terrain.js`
 struct Adjacencies {
-    adjacent: Vec<Vec<usize>>,
+    adjacent: Graph,
     voronoi_triangles: Vec<usize>,
-    voronoi_points: Vec<Vec<usize>>,
-    voronoi_cells: Vec<Vec<usize>>,
+    voronoi_points: Graph,
+    voronoi_cells: Graph,
 }
 
 impl Voronoi {
     // Adapted from:
     //     https://github.com/d3/d3-delaunay/blob/master/src/voronoi.js
     //     https://github.com/d3/d3-delaunay/blob/master/src/delaunay.js
-    pub fn new(points: Vec<f64> /*, xmin: f64, ymin: f64, xmax: f64, ymax: f64*/) -> Voronoi {
+    pub fn new(
+        points: Vec<f64>, /*, xmin: f64, ymin: f64, xmax: f64, ymax: f64*/
+    ) -> Result<Voronoi, TerrainError> {
         utils::set_panic_hook();
         let Triangulation {
             triangles,
             halfedges,
             hull,
-        } = Voronoi::triangulate(&points);
+        } = Voronoi::triangulate(&points)?;
         let inedges = Voronoi::get_inedges(&points, &halfedges, &triangles);
         let neighbors = Voronoi::get_neighbors(&points, &inedges, &hull, &halfedges, &triangles);
         let circumcenters = Voronoi::get_circumcenters(&points, &triangles);
@@ -64,8 +68,7 @@ impl Voronoi {
             voronoi_triangles,
             voronoi_points,
             voronoi_cells,
-        } = Voronoi::get_adjacencies(&points, &circumcenters, &inedges, &halfedges, &triangles)
-            .unwrap();
+        } = Voronoi::get_adjacencies(&points, &circumcenters, &inedges, &halfedges, &triangles)?;
 
         let delaunay = Delaunay {
             points,
@@ -76,24 +79,24 @@ impl Voronoi {
             neighbors,
         };
 
-        Voronoi {
+        Ok(Voronoi {
             circumcenters,
             delaunay,
             adjacent,
             voronoi_triangles,
             voronoi_points,
             voronoi_cells,
-        }
+        })
     }
 
-    fn triangulate(points: &Vec<f64>) -> Triangulation {
+    fn triangulate(points: &Vec<f64>) -> Result<Triangulation, TerrainError> {
         let struct_points: Vec<&[f64]> = points.chunks_exact(2).collect();
         let struct_points = struct_points
             .iter()
             .map(|p| Point { x: p[0], y: p[1] })
             .collect::<Vec<_>>();
 
-        triangulate(&struct_points).unwrap()
+        triangulate(&struct_points).ok_or(TerrainError::Triangulation)
     }
 
     fn get_inedges(
@@ -169,34 +172,25 @@ impl Voronoi {
         inedges: &Vec<usize>,
         halfedges: &Vec<usize>,
         triangles: &Vec<usize>,
-    ) -> Result<Adjacencies, String> {
-        let mut adjacent = vec![Vec::new(); circumcenters.len() / 2];
+    ) -> Result<Adjacencies, TerrainError> {
+        let n = circumcenters.len() / 2;
         let mut voronoi_triangles = Vec::new();
-        let mut voronoi_points = vec![Vec::new(); points.len() / 2];
-        let mut voronoi_cells = vec![Vec::new(); circumcenters.len() / 2];
+        let mut voronoi_points = Graph::with_capacity(points.len() / 2, triangles.len());
+        let mut cell = Vec::new();
 
-        for i in 0..inedges.len() {
-            let e0 = inedges[i];
+        for (i, &e0) in inedges.iter().enumerate() {
             if e0 == EMPTY {
-                return Err("Coincident point".to_string());
-            } // coincident point
+                return Err(TerrainError::CoincidentPoint(i));
+            }
             let mut e = e0;
             let mut t;
             let mut previous_t = EMPTY;
 
             loop {
                 t = e / 3;
-                voronoi_cells[t].push(i);
-                voronoi_points[i].push(t);
+                cell.push(t);
 
-                // Index `t` is neighbour of the previous `t`
                 if previous_t != EMPTY {
-                    if !adjacent[t].contains(&previous_t) {
-                        adjacent[t].push(previous_t);
-                    }
-                    if !adjacent[previous_t].contains(&t) {
-                        adjacent[previous_t].push(t);
-                    }
                     voronoi_triangles.extend([i, t, previous_t].iter());
                 }
                 previous_t = t;
@@ -213,6 +207,24 @@ impl Voronoi {
             }
 
             voronoi_triangles.extend([i, e / 3, previous_t].iter());
+            voronoi_points.push(cell.drain(..));
+        }
+
+        // Voronoi vertices are neighbours when their triangles share an edge,
+        // and each one is a corner of the three cells around its triangle.
+        let mut adjacent = Graph::with_capacity(n, n * 3);
+        let mut voronoi_cells = Graph::with_capacity(n, n * 3);
+        for t in 0..n {
+            adjacent.push(
+                halfedges[t * 3..t * 3 + 3]
+                    .iter()
+                    .filter(|&&e| e != EMPTY)
+                    .map(|&e| e / 3),
+            );
+
+            let mut corners = [triangles[t * 3], triangles[t * 3 + 1], triangles[t * 3 + 2]];
+            corners.sort_unstable();
+            voronoi_cells.push(corners.iter().cloned());
         }
 
         Ok(Adjacencies {
@@ -229,13 +241,14 @@ impl Voronoi {
         hull: &Vec<usize>,
         halfedges: &Vec<usize>,
         triangles: &Vec<usize>,
-    ) -> Vec<Vec<usize>> {
-        let mut neighbors = vec![Vec::new(); points.len() / 2];
+    ) -> Graph {
+        let mut neighbors = Graph::with_capacity(points.len() / 2, halfedges.len());
         let mut hull_index = vec![EMPTY; points.len() / 2];
         for i in 0..hull.len() {
             hull_index[hull[i]] = i;
         }
 
+        let mut list = Vec::new();
         for i in 0..points.len() / 2 {
             let e0 = inedges[i];
             let mut e = e0;
@@ -243,14 +256,14 @@ impl Voronoi {
 
             loop {
                 p0 = triangles[e];
-                neighbors[i].push(p0);
+                list.push(p0);
                 e = if e % 3 == 2 { e - 2 } else { e + 1 };
 
                 e = halfedges[e];
                 if e == EMPTY {
                     let p = hull[(hull_index[i] + 1) % hull.len()];
                     if p != p0 {
-                        neighbors[i].push(p);
+                        list.push(p);
                     }
                     break;
                 }
@@ -258,6 +271,7 @@ impl Voronoi {
                     break;
                 }
             }
+            neighbors.push(list.drain(..));
         }
         neighbors
     }
@@ -0,0 +1,319 @@
+//! Building a world stage by stage must give the same world as building it in
+//! one go.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use terrain_generator::terrain_generator::{Stage, TerrainGenerator, WorldBuilder, WorldOptions};
+
+const SEED: u32 = 7;
+const SEA_LEVEL: f64 = 0.39;
+
+fn radius() -> f64 {
+    (500.0_f64 / 512.).sqrt() / 10.0
+}
+
+fn options() -> WorldOptions {
+    WorldOptions {
+        erosion_passes: 3,
+        ..WorldOptions::new(SEA_LEVEL)
+    }
+}
+
+#[test]
+fn stepping_matches_build() {
+    let built = WorldBuilder::with_options(radius(), options())
+        .build(&mut TerrainGenerator::new(Some(SEED)))
+        .unwrap();
+
+    let mut generator = TerrainGenerator::new(Some(SEED));
+    let mut builder = WorldBuilder::with_options(radius(), options());
+    let mut stages = vec![builder.stage()];
+    let mut progress = vec![builder.progress()];
+    while !builder.step(&mut generator).unwrap() {
+        stages.push(builder.stage());
+        progress.push(builder.progress());
+    }
+    stages.push(builder.stage());
+    progress.push(builder.progress());
+
+    assert_eq!(stages.first(), Some(&Stage::Sample));
+    assert_eq!(stages.last(), Some(&Stage::Done));
+    assert!(stages.windows(2).all(|w| w[0] <= w[1]));
+    // Three erosion passes stay on `Erode`, every other step moves on
+    assert_eq!(stages.iter().filter(|&&s| s == Stage::Erode).count(), 3);
+
+    assert_eq!(progress.first(), Some(&0.));
+    assert_eq!(progress.last(), Some(&1.));
+    assert!(progress.windows(2).all(|w| w[0] < w[1]), "{:?}", progress);
+
+    // Stepping once done changes nothing
+    assert!(builder.step(&mut generator).unwrap());
+    assert_eq!(builder.progress(), 1.);
+    assert!(builder.finish(&mut generator).unwrap() == built);
+}
+
+#[test]
+fn rewound_matches_fresh_build() {
+    let base = options();
+    let changes: Vec<(&str, WorldOptions)> = vec![
+        (
+            "sea_level",
+            WorldOptions {
+                sea_level: 0.45,
+                ..base
+            },
+        ),
+        (
+            "erosion_passes",
+            WorldOptions {
+                erosion_passes: 5,
+                ..base
+            },
+        ),
+        (
+            "erosion_rate",
+            WorldOptions {
+                erosion_rate: 0.03,
+                ..base
+            },
+        ),
+        (
+            "river_threshold",
+            WorldOptions {
+                river_threshold: 5.,
+                ..base
+            },
+        ),
+        (
+            "river_min_length",
+            WorldOptions {
+                river_min_length: 4,
+                ..base
+            },
+        ),
+        (
+            "river_min_order",
+            WorldOptions {
+                river_min_order: 2,
+                ..base
+            },
+        ),
+        (
+            "rivers_reach_water",
+            WorldOptions {
+                rivers_reach_water: true,
+                ..base
+            },
+        ),
+        (
+            "river_mouths",
+            WorldOptions {
+                river_mouths: false,
+                ..base
+            },
+        ),
+        (
+            "mouth_min_discharge",
+            WorldOptions {
+                mouth_min_discharge: 0.001,
+                ..base
+            },
+        ),
+        (
+            "delta_threshold",
+            WorldOptions {
+                delta_threshold: 0.01,
+                ..base
+            },
+        ),
+        (
+            "coast_erosion_passes",
+            WorldOptions {
+                coast_erosion_passes: 2,
+                ..base
+            },
+        ),
+        (
+            "bathymetry",
+            WorldOptions {
+                bathymetry: false,
+                ..base
+            },
+        ),
+        (
+            "trenches",
+            WorldOptions {
+                trenches: true,
+                ..base
+            },
+        ),
+        (
+            "south_latitude",
+            WorldOptions {
+                south_latitude: -30.,
+                ..base
+            },
+        ),
+        (
+            "north_latitude",
+            WorldOptions {
+                north_latitude: 40.,
+                ..base
+            },
+        ),
+        (
+            "axial_tilt",
+            WorldOptions {
+                axial_tilt: 45.,
+                ..base
+            },
+        ),
+        (
+            "glacial_erosion",
+            WorldOptions {
+                glacial_erosion: true,
+                ..base
+            },
+        ),
+    ];
+
+    let mut generator = TerrainGenerator::new(Some(SEED));
+    let mut builder = WorldBuilder::with_options(radius(), base);
+    builder.finish(&mut generator).unwrap();
+
+    for (name, changed) in changes {
+        let fresh = WorldBuilder::with_options(radius(), changed)
+            .build(&mut TerrainGenerator::new(Some(SEED)))
+            .unwrap();
+        builder.set_options(changed);
+        let rewound = builder.finish(&mut generator).unwrap();
+        assert!(rewound == fresh, "Changing {} gave another world", name);
+        assert_eq!(builder.progress(), 1.);
+
+        // And back again
+        builder.set_options(base);
+        builder.finish(&mut generator).unwrap();
+    }
+}
+
+#[test]
+fn changed_midway_matches_fresh_build() {
+    let base = options();
+    let changes = vec![
+        (
+            "erosion_passes",
+            WorldOptions {
+                erosion_passes: 0,
+                ..base
+            },
+        ),
+        (
+            "erosion_passes",
+            WorldOptions {
+                erosion_passes: 5,
+                ..base
+            },
+        ),
+        (
+            "erosion_rate",
+            WorldOptions {
+                erosion_rate: 0.03,
+                ..base
+            },
+        ),
+        (
+            "bathymetry",
+            WorldOptions {
+                bathymetry: false,
+                ..base
+            },
+        ),
+        (
+            "glacial_erosion",
+            WorldOptions {
+                glacial_erosion: true,
+                ..base
+            },
+        ),
+        (
+            "river_threshold",
+            WorldOptions {
+                river_threshold: 5.,
+                ..base
+            },
+        ),
+        (
+            "river_mouths",
+            WorldOptions {
+                river_mouths: false,
+                ..base
+            },
+        ),
+        (
+            "coast_erosion_passes",
+            WorldOptions {
+                coast_erosion_passes: 2,
+                ..base
+            },
+        ),
+        (
+            "axial_tilt",
+            WorldOptions {
+                axial_tilt: 45.,
+                ..base
+            },
+        ),
+    ];
+    let stages = [
+        Stage::Erode,
+        Stage::CellHeights,
+        Stage::Bathymetry,
+        Stage::Glaciers,
+        Stage::Rivers,
+        Stage::Mouths,
+        Stage::CoastErosion,
+        Stage::Coasts,
+        Stage::Climate,
+        Stage::Vegetation,
+        Stage::Resources,
+    ];
+
+    for (name, changed) in changes {
+        let fresh = WorldBuilder::with_options(radius(), changed)
+            .build(&mut TerrainGenerator::new(Some(SEED)))
+            .unwrap();
+
+        for &stage in stages.iter() {
+            let mut generator = TerrainGenerator::new(Some(SEED));
+            let mut builder = WorldBuilder::with_options(radius(), base);
+            while builder.stage() < stage {
+                builder.step(&mut generator).unwrap();
+            }
+            // Partway through erosion, after its first pass
+            if stage == Stage::Erode {
+                builder.step(&mut generator).unwrap();
+                assert_eq!(builder.stage(), Stage::Erode);
+            }
+
+            builder.set_options(changed);
+            let mut progress = vec![builder.progress()];
+            while !builder.step(&mut generator).unwrap() {
+                progress.push(builder.progress());
+            }
+            assert!(
+                progress.windows(2).all(|w| w[0] < w[1]) && builder.progress() == 1.,
+                "Changing {} at {:?} gave progress {:?}",
+                name,
+                stage,
+                progress
+            );
+            let rewound = builder.finish(&mut generator).unwrap();
+            assert!(
+                rewound == fresh,
+                "Changing {} at {:?} gave another world",
+                name,
+                stage
+            );
+        }
+    }
+}
@@ -0,0 +1,23 @@
+//! Detailed coast rings must refuse detail that would blow up their size.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use terrain_generator::error::TerrainError;
+use terrain_generator::terrain_generator::{CoastDetailOptions, TerrainGenerator};
+
+#[test]
+fn coast_levels_are_bounded() {
+    let radius = (500.0_f64 / 256.).sqrt() / 10.0;
+    let world = TerrainGenerator::new(Some(7)).world(radius, 0.39).unwrap();
+    let rings = |levels| {
+        world.coast_rings(&CoastDetailOptions {
+            levels,
+            ..CoastDetailOptions::default()
+        })
+    };
+
+    let finest = rings(8).unwrap();
+    assert!(finest.iter().all(|ring| ring.samples == 256));
+    assert_eq!(rings(9), Err(TerrainError::InvalidOption("levels")));
+    assert_eq!(rings(64), Err(TerrainError::InvalidOption("levels")));
+}
@@ -0,0 +1,109 @@
+//! Fixed seeds must give the same worlds across versions and platforms.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::*;
+
+use terrain_generator::terrain_generator::{TerrainGenerator, World};
+
+const GOLDEN: &str = include_str!("golden.txt");
+const SEA_LEVEL: f64 = 0.39;
+
+fn generate(seed: u32, points: u32) -> World {
+    let radius = (500.0 / points as f64).sqrt() / 10.0;
+    TerrainGenerator::new(Some(seed))
+        .world(radius, SEA_LEVEL)
+        .unwrap()
+}
+
+fn golden() -> Vec<(u32, u32, Option<String>)> {
+    GOLDEN
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let columns = line.split_whitespace().collect::<Vec<_>>();
+            let fingerprint = match columns[2] {
+                "-" => None,
+                fingerprint => Some(fingerprint.to_string()),
+            };
+            (
+                columns[0].parse().unwrap(),
+                columns[1].parse().unwrap(),
+                fingerprint,
+            )
+        })
+        .collect()
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+fn same_seed_same_world() {
+    let a = generate(7, 1024);
+    let b = generate(7, 1024);
+    assert_eq!(a.fingerprint(), b.fingerprint());
+    assert!(a == b);
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+fn save_load_round_trip() {
+    let world = generate(7, 1024);
+    for &compress in [false, true].iter() {
+        let loaded = World::load(&world.save(compress)).unwrap();
+        assert_eq!(loaded.fingerprint(), world.fingerprint());
+        // Derived fields aren't in the fingerprint, so compare them too
+        assert!(loaded == world);
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+fn matches_golden_fingerprints() {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        if std::env::var_os("TERRAIN_BLESS").is_some() {
+            return bless();
+        }
+    }
+
+    let mut drifted = Vec::new();
+    for (seed, points, expected) in golden() {
+        let actual = generate(seed, points).fingerprint();
+        match expected {
+            Some(expected) if expected != actual => {
+                drifted.push(format!(
+                    "seed {} with {} points: expected {}, got {}",
+                    seed, points, expected, actual
+                ));
+            }
+            Some(_) => {}
+            None => drifted.push(format!(
+                "seed {} with {} points: no golden fingerprint, run with TERRAIN_BLESS=1",
+                seed, points
+            )),
+        }
+    }
+    assert!(
+        drifted.is_empty(),
+        "Worlds drifted:\n{}",
+        drifted.join("\n")
+    );
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn bless() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden.txt");
+    let mut lines = GOLDEN
+        .lines()
+        .take_while(|line| line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect::<Vec<_>>();
+    for (seed, points, _) in golden() {
+        lines.push(format!(
+            "{} {} {}",
+            seed,
+            points,
+            generate(seed, points).fingerprint()
+        ));
+    }
+    std::fs::write(path, lines.join("\n") + "\n").unwrap();
+}
@@ -0,0 +1,115 @@
+//! GeoJSON export must be valid RFC 7946 and agree with the world it came from.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use serde_json::Value;
+use terrain_generator::terrain_generator::TerrainGenerator;
+
+fn generate() -> (Value, Value) {
+    let radius = (500.0_f64 / 1024.).sqrt() / 10.0;
+    let world = TerrainGenerator::new(Some(7)).world(radius, 0.39).unwrap();
+    let json = serde_json::to_value(&world).unwrap();
+    let geojson = serde_json::from_str(&world.to_geojson()).unwrap();
+    (json, geojson)
+}
+
+fn features<'a>(geojson: &'a Value, kind: &'a str) -> impl Iterator<Item = &'a Value> {
+    geojson["features"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(move |f| f["properties"]["kind"] == kind)
+}
+
+#[test]
+fn features_are_well_formed() {
+    let (_, geojson) = generate();
+    assert_eq!(geojson["type"], "FeatureCollection");
+
+    for feature in geojson["features"].as_array().unwrap() {
+        assert_eq!(feature["type"], "Feature");
+        let geometry = &feature["geometry"];
+        let coordinates = &geometry["coordinates"];
+        match geometry["type"].as_str().unwrap() {
+            "Point" => assert_eq!(coordinates.as_array().unwrap().len(), 2),
+            "LineString" => assert!(coordinates.as_array().unwrap().len() >= 2),
+            "MultiLineString" => {
+                for line in coordinates.as_array().unwrap() {
+                    assert!(line.as_array().unwrap().len() >= 2);
+                }
+            }
+            "Polygon" => {
+                for ring in coordinates.as_array().unwrap() {
+                    let ring = ring.as_array().unwrap();
+                    assert!(ring.len() >= 4, "Rings need at least four positions");
+                    assert_eq!(ring.first(), ring.last(), "Rings must be closed");
+                }
+            }
+            other => panic!("Unexpected geometry {}", other),
+        }
+    }
+}
+
+#[test]
+fn every_cell_is_a_polygon() {
+    let (json, geojson) = generate();
+    let cells = json["cellHeights"].as_array().unwrap().len();
+    let hull = json["voronoi"]["delaunay"]["hull"].as_array().unwrap();
+
+    let mut indices = features(&geojson, "cell")
+        .map(|f| f["properties"]["index"].as_u64().unwrap() as usize)
+        .collect::<Vec<_>>();
+    indices.sort_unstable();
+    assert_eq!(indices, (0..cells).collect::<Vec<_>>());
+
+    // Hull cells close through their site
+    for feature in features(&geojson, "cell") {
+        let index = &feature["properties"]["index"];
+        if hull.contains(index) {
+            let i = index.as_u64().unwrap() as usize;
+            let points = &json["voronoi"]["delaunay"]["points"];
+            let site = [
+                points[i * 2].as_f64().unwrap(),
+                points[i * 2 + 1].as_f64().unwrap(),
+            ];
+            let ring = feature["geometry"]["coordinates"][0].as_array().unwrap();
+            assert!(ring.iter().any(|p| {
+                let x = p[0].as_f64().unwrap() - site[0];
+                let y = p[1].as_f64().unwrap() - site[1];
+                x.abs() < 1e-12 && y.abs() < 1e-12
+            }));
+        }
+    }
+}
+
+#[test]
+fn rivers_carry_their_mouth_values() {
+    let (json, geojson) = generate();
+    let rivers = json["rivers"].as_array().unwrap();
+    let streams = json["riverNetwork"]["streams"].as_array().unwrap();
+    let features = features(&geojson, "river").collect::<Vec<_>>();
+    assert!(!rivers.is_empty());
+    assert_eq!(features.len(), rivers.len());
+
+    for ((feature, river), stream) in features.iter().zip(rivers).zip(streams) {
+        let properties = &feature["properties"];
+        let line = feature["geometry"]["coordinates"].as_array().unwrap();
+        assert_eq!(line.len(), river.as_array().unwrap().len());
+        assert_eq!(properties["strahler"], stream["strahler"][0]);
+        assert_eq!(properties["shreve"], stream["shreve"][0]);
+        assert_eq!(properties["discharge"], stream["discharge"][0]);
+
+        let strahler = stream["strahler"].as_array().unwrap();
+        let most = strahler.iter().map(|s| s.as_u64().unwrap()).max().unwrap();
+        assert_eq!(properties["strahler"].as_u64().unwrap(), most);
+    }
+}
+
+#[test]
+fn deposits_and_coasts_are_exported() {
+    let (json, geojson) = generate();
+    let deposits = json["resources"]["deposits"].as_array().unwrap();
+    assert_eq!(features(&geojson, "deposit").count(), deposits.len());
+    // One multi-line per kind of shore
+    assert_eq!(features(&geojson, "coast").count(), 4);
+}
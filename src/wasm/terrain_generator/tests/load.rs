@@ -0,0 +1,79 @@
+//! Corrupt saved worlds must fail to load instead of panicking.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use terrain_generator::terrain_generator::{TerrainGenerator, World};
+
+// A version 4 world with no points, and `rivers`, `coasts` and `deposits` as
+// its only index sections
+fn empty_world(rivers: &[usize], coasts: &[usize], deposits: &[usize]) -> Vec<u8> {
+    let mut bytes = b"TGW\0\x04\x00".to_vec();
+    // Delaunay and Voronoi sections, then heights and cell heights
+    bytes.extend(vec![0; 13]);
+    bytes.push(1);
+    bytes.push(rivers.len() as u8);
+    bytes.extend(rivers.iter().map(|&i| i as u8));
+    bytes.push(rivers.len() as u8);
+    bytes.extend(rivers.iter().flat_map(|_| 0f64.to_le_bytes().to_vec()));
+    bytes.push(coasts.len() as u8);
+    bytes.extend(coasts.iter().map(|&i| i as u8));
+    bytes.extend(&0.39f64.to_le_bytes());
+    // Mouths, then the climate options
+    bytes.extend(vec![0; 5]);
+    bytes.extend((0..3).flat_map(|_| 0f64.to_le_bytes().to_vec()));
+    // Fertility, then deposit kinds, cells and richness
+    bytes.push(0);
+    bytes.push(deposits.len() as u8);
+    bytes.extend(deposits.iter().map(|_| 0));
+    bytes.push(deposits.len() as u8);
+    bytes.extend(deposits.iter().map(|&i| i as u8));
+    bytes.push(deposits.len() as u8);
+    bytes.extend(deposits.iter().flat_map(|_| 1f64.to_le_bytes().to_vec()));
+    bytes
+}
+
+#[test]
+fn empty_world_loads() {
+    assert!(World::load(&empty_world(&[], &[], &[])).is_ok());
+}
+
+#[test]
+fn truncated_worlds_fail() {
+    let radius = (500.0_f64 / 64.).sqrt() / 10.0;
+    let world = TerrainGenerator::new(Some(7)).world(radius, 0.39).unwrap();
+    for &compress in [false, true].iter() {
+        let bytes = world.save(compress);
+        for end in (0..bytes.len()).step_by(7) {
+            assert!(World::load(&bytes[..end]).is_err(), "Loaded {} bytes", end);
+        }
+    }
+}
+
+#[test]
+fn out_of_range_indices_fail() {
+    for bytes in [
+        empty_world(&[3], &[], &[]),
+        empty_world(&[], &[0, 1], &[]),
+        empty_world(&[], &[], &[2]),
+    ]
+    .iter()
+    {
+        let error = World::load(bytes).err().unwrap().to_string();
+        assert!(error.contains("out of range"), "{}", error);
+    }
+}
+
+#[test]
+fn odd_coast_lines_fail() {
+    assert!(World::load(&empty_world(&[], &[0], &[])).is_err());
+}
+
+#[test]
+fn oversized_bodies_fail() {
+    // Under a megabyte that would inflate to 100 MB
+    let mut bytes = b"TGW\0\x05\x01".to_vec();
+    bytes.extend(miniz_oxide::deflate::compress_to_vec(&vec![0; 100 << 20], 6));
+    assert!(bytes.len() < 1 << 20);
+    let error = World::load(&bytes).err().unwrap().to_string();
+    assert!(error.contains("Corrupt compressed world"), "{}", error);
+}
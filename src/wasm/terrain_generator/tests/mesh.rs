@@ -0,0 +1,47 @@
+//! GLB export must follow the glTF 2.0 binary container layout.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use serde_json::Value;
+use terrain_generator::terrain_generator::TerrainGenerator;
+
+fn u32_at(bytes: &[u8], offset: usize) -> usize {
+    let mut word = [0; 4];
+    word.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(word) as usize
+}
+
+#[test]
+fn glb_header_and_chunks() {
+    let radius = (500.0_f64 / 1024.).sqrt() / 10.0;
+    let world = TerrainGenerator::new(Some(7)).world(radius, 0.39).unwrap();
+    let glb = world.to_glb();
+
+    // Header: magic, version and total length
+    assert_eq!(&glb[0..4], b"glTF");
+    assert_eq!(u32_at(&glb, 4), 2);
+    assert_eq!(u32_at(&glb, 8), glb.len());
+
+    // JSON chunk first, then BIN, each padded to 4 bytes
+    let json_length = u32_at(&glb, 12);
+    assert_eq!(&glb[16..20], b"JSON");
+    assert_eq!(json_length % 4, 0);
+    let json: Value = serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+
+    let bin_start = 20 + json_length;
+    let bin_length = u32_at(&glb, bin_start);
+    assert_eq!(&glb[bin_start + 4..bin_start + 8], b"BIN\0");
+    assert_eq!(bin_length % 4, 0);
+    assert_eq!(bin_start + 8 + bin_length, glb.len());
+
+    // Buffer views fit the BIN chunk and start 4-byte aligned
+    let buffer = json["buffers"][0]["byteLength"].as_u64().unwrap() as usize;
+    assert!(buffer <= bin_length && bin_length - buffer < 4);
+    for view in json["bufferViews"].as_array().unwrap() {
+        let offset = view["byteOffset"].as_u64().unwrap() as usize;
+        let length = view["byteLength"].as_u64().unwrap() as usize;
+        assert_eq!(offset % 4, 0);
+        assert!(offset + length <= buffer);
+    }
+    assert!(!json["meshes"].as_array().unwrap().is_empty());
+}
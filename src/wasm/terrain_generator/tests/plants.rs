@@ -0,0 +1,76 @@
+//! Plants must stand inside the cells they're listed in, and follow their seed.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use serde_json::Value;
+use terrain_generator::terrain_generator::{PlantOptions, TerrainGenerator};
+
+fn f64s(value: &Value) -> Vec<f64> {
+    value
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v.as_f64().unwrap())
+        .collect()
+}
+
+fn indices(value: &Value) -> Vec<usize> {
+    value
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v.as_u64().unwrap() as usize)
+        .collect()
+}
+
+#[test]
+fn plants_stand_in_their_cells() {
+    let radius = (500.0_f64 / 1024.).sqrt() / 10.0;
+    let world = TerrainGenerator::new(Some(7)).world(radius, 0.39).unwrap();
+    let plants = world.plants(&PlantOptions {
+        seed: 3,
+        ..PlantOptions::default()
+    });
+    assert!(!plants.positions.is_empty());
+    assert_eq!(plants.kinds.len(), plants.positions.len());
+    assert_eq!(plants.cells.len(), plants.positions.len());
+
+    let json = serde_json::to_value(&world).unwrap();
+    let circumcenters = f64s(&json["voronoi"]["circumcenters"]);
+    let offsets = indices(&json["voronoi"]["voronoi_points"]["offsets"]);
+    let values = indices(&json["voronoi"]["voronoi_points"]["values"]);
+
+    for (p, &c) in plants.positions.iter().zip(plants.cells.iter()) {
+        // Cells are convex, so inside is on the same side of every edge
+        let corners = values[offsets[c]..offsets[c + 1]]
+            .iter()
+            .map(|&t| [circumcenters[t * 2], circumcenters[t * 2 + 1]])
+            .collect::<Vec<_>>();
+        let turns = corners
+            .iter()
+            .zip(corners.iter().cycle().skip(1))
+            .map(|(a, b)| (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0]))
+            .collect::<Vec<_>>();
+        assert!(
+            turns.iter().all(|&t| t >= 0.) || turns.iter().all(|&t| t <= 0.),
+            "plant at {:?} outside cell {}",
+            p,
+            c
+        );
+    }
+}
+
+#[test]
+fn plants_follow_the_seed() {
+    let radius = (500.0_f64 / 1024.).sqrt() / 10.0;
+    let world = TerrainGenerator::new(Some(7)).world(radius, 0.39).unwrap();
+    let plants = |seed| {
+        world.plants(&PlantOptions {
+            seed,
+            ..PlantOptions::default()
+        })
+    };
+
+    assert_eq!(plants(3), plants(3));
+    assert_ne!(plants(3).positions, plants(4).positions);
+}
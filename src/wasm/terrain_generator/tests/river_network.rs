@@ -0,0 +1,73 @@
+//! Stream orders and discharge on a hand-built network, small enough to work
+//! out on paper.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use terrain_generator::river_network::RiverNetwork;
+
+// Two sources, 2 and 3, meet at 1 and flow on to the mouth at 0:
+//
+//     2
+//      \
+//       1 -- 0
+//      /
+//     3
+//
+// Flux counts the circumcenters draining through each node.
+fn fork() -> RiverNetwork {
+    RiverNetwork::new(&vec![
+        vec![(0, 3.), (1, 2.), (2, 0.)],
+        vec![(1, 2.), (3, 0.)],
+    ])
+}
+
+#[test]
+fn streams_are_linked() {
+    let network = fork();
+    let [trunk, tributary] = [&network.streams[0], &network.streams[1]];
+    assert_eq!(trunk.parent, None);
+    assert_eq!(trunk.tributaries, vec![1]);
+    assert_eq!(tributary.parent, Some((0, 1)));
+    assert!(tributary.tributaries.is_empty());
+    assert_eq!((trunk.mouth, tributary.mouth), (0, 0));
+    assert_eq!(network.mouths(), vec![0]);
+}
+
+#[test]
+fn two_first_order_streams_make_a_second_order_one() {
+    let network = fork();
+    let [trunk, tributary] = [&network.streams[0], &network.streams[1]];
+
+    // Orders rise at the confluence, and never drop downstream
+    assert_eq!(trunk.strahler, vec![2, 2, 1]);
+    assert_eq!(trunk.shreve, vec![2, 2, 1]);
+    // The confluence carries the tributary's own values
+    assert_eq!(tributary.strahler, vec![1, 1]);
+    assert_eq!(tributary.shreve, vec![1, 1]);
+}
+
+#[test]
+fn discharge_adds_up_at_confluences() {
+    let network = fork();
+    let [trunk, tributary] = [&network.streams[0], &network.streams[1]];
+
+    // Each source drains itself, the confluence both sources and itself, and
+    // the mouth everything
+    assert_eq!(trunk.discharge, vec![4., 3., 1.]);
+    assert_eq!(tributary.discharge, vec![1., 1.]);
+}
+
+#[test]
+fn a_lower_order_tributary_keeps_the_order() {
+    // Another source, 5, joins the trunk of `fork` at its mouth through 4
+    let network = RiverNetwork::new(&vec![
+        vec![(0, 5.), (1, 2.), (2, 0.)],
+        vec![(1, 2.), (3, 0.)],
+        vec![(0, 5.), (4, 0.)],
+    ]);
+    let trunk = &network.streams[0];
+    assert_eq!(trunk.tributaries, vec![2, 1]);
+    assert_eq!(trunk.strahler[0], 2);
+    assert_eq!(trunk.shreve[0], 3);
+    assert_eq!(trunk.discharge[0], 6.);
+}
@@ -0,0 +1,48 @@
+//! River extraction must not depend on the call stack, which is small in wasm,
+//! and meanders must follow from the world alone.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use terrain_generator::terrain_generator::{RiverCurveOptions, TerrainGenerator, World};
+
+// A recursive walk overflows this with as few as 4096 points
+const STACK_SIZE: usize = 64 * 1024;
+
+#[test]
+fn long_rivers_fit_on_a_small_stack() {
+    let longest = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| {
+            let radius = (500.0_f64 / 16384.).sqrt() / 10.0;
+            let world = TerrainGenerator::new(Some(9)).world(radius, 0.39).unwrap();
+
+            let world = serde_json::to_value(&world).unwrap();
+            world["rivers"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|river| river.as_array().unwrap().len())
+                .max()
+                .unwrap()
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+
+    assert!(longest > 50, "Longest river: {} nodes", longest);
+}
+
+#[test]
+fn meanders_follow_the_seed() {
+    let radius = (500.0_f64 / 1024.).sqrt() / 10.0;
+    let curves = |seed| {
+        let world = TerrainGenerator::new(Some(seed))
+            .world(radius, 0.39)
+            .unwrap();
+        let curves = world.river_curves(&RiverCurveOptions::default());
+        let loaded = World::load(&world.save(true)).unwrap();
+        assert_eq!(loaded.river_curves(&RiverCurveOptions::default()), curves);
+        curves
+    };
+    assert_eq!(curves(3), curves(3));
+}